@@ -1,5 +1,6 @@
 #include <cassert>
 #include <cctype>
+#include <climits>
 
 #include <iostream>
 #include <string>
@@ -7,11 +8,51 @@
 #include <initializer_list>
 #include <algorithm>
 #include <numeric>
+#include <functional>
+#include <map>
+#include <set>
+#include <sstream>
+#include <memory>
+#include <utility>
+
+// Width of every element and whether it is signed, see `--bits` and
+// `--signed`. Elements are stored as `unsigned`, wrapped to this width after
+// each operation; signed ones are in two's complement.
+#ifndef HERANG_BITS
+#define HERANG_BITS 8
+#endif
+#ifndef HERANG_SIGNED
+#define HERANG_SIGNED 0
+#endif
+static_assert(HERANG_BITS == 8 || HERANG_BITS == 16 || HERANG_BITS == 32, "HERANG_BITS must be 8, 16 or 32");
 
 using Int = unsigned;
 
+constexpr Int MASK = ~Int(0) >> (32 - HERANG_BITS);
+constexpr Int wrap(Int x) { return x & MASK; }
+// The number an element stands for.
+constexpr long long number(Int x) {
+    return HERANG_SIGNED && wrap(x) > (MASK >> 1) ? (long long)wrap(x) - MASK - 1 : (long long)wrap(x);
+}
+// Sign-extended to 32 bits, so `int` arithmetic sees negative numbers.
+constexpr int sext(Int x) { return int(number(x)); }
+
+class u8;
+// What a function value holds; arguments are passed as a vector.
+using Function = std::function<u8(const std::vector<u8> &)>;
+using Map = std::map<std::vector<Int>, u8>;
+
 class u8 : public std::vector<Int> {
 public:
+    // Set when the value is a function instead of numbers.
+    std::shared_ptr<Function> func;
+    // Set when the value is a list of `items` instead of numbers.
+    bool is_list = false;
+    std::vector<u8> items;
+    // Set when the value is a map; shared between copies until one of them
+    // is modified.
+    std::shared_ptr<Map> entries;
+
     u8() = default;
     u8(const u8 &) = default;
     u8(u8 &&) = default;
@@ -23,39 +64,119 @@ public:
     u8 &operator=(u8 &&) = default;
     ~u8() = default;
 
+    static u8 make_list(std::vector<u8> items) {
+        u8 v;
+        v.is_list = true;
+        v.items = std::move(items);
+        return v;
+    }
+
+    // An operand that is not a list is an item on its own next to a list.
     friend u8 operator|(const u8 &lhs, const u8 &rhs) {
+        if (lhs.is_list || rhs.is_list) {
+            u8 v = lhs.is_list ? lhs : make_list({lhs});
+            if (rhs.is_list) v.items.insert(v.items.end(), rhs.items.begin(), rhs.items.end());
+            else v.items.push_back(rhs);
+            return v;
+        }
+        assert(!lhs.rank() && !rhs.rank());
         u8 v = lhs;
         v.insert(v.end(), rhs.begin(), rhs.end());
         return v;
     }
-    friend u8 operator+(const u8 &lhs, const u8 &rhs) {
-        if (lhs.size() > rhs.size()) return rhs + lhs;
-        u8 v = lhs;
-        for (std::size_t i = 0; i < lhs.size(); ++i) v.at(i) += rhs.at(i);
+    // Element by element; a single-element operand is repeated to match the
+    // other one, any other length mismatch is an error.
+    template <typename F>
+    friend u8 zip_with(const u8 &lhs, const u8 &rhs, F f) {
+        std::size_t n = lhs.size() == 1 ? rhs.size() : lhs.size();
+        assert(!lhs.rank() && !rhs.rank());
+        assert(lhs.size() == rhs.size() || lhs.size() == 1 || rhs.size() == 1);
+        u8 v;
+        for (std::size_t i = 0; i < n; ++i) {
+            v.push_back(wrap(f(lhs.at(lhs.size() == 1 ? 0 : i), rhs.at(rhs.size() == 1 ? 0 : i))));
+        }
         return v;
     }
+    friend u8 operator+(const u8 &lhs, const u8 &rhs) {
+        return zip_with(lhs, rhs, [](Int x, Int y) { return x + y; });
+    }
     friend u8 operator-(const u8 &lhs, const u8 &rhs) {
-        if (lhs.size() > rhs.size()) return rhs - lhs;
-        u8 v = lhs;
-        for (std::size_t i = 0; i < lhs.size(); ++i) v.at(i) -= rhs.at(i);
-        return v;
+        return zip_with(lhs, rhs, [](Int x, Int y) { return x - y; });
     }
     friend u8 operator*(const u8 &lhs, const u8 &rhs) {
-        if (lhs.size() > rhs.size()) return rhs * lhs;
-        u8 v = lhs;
-        for (std::size_t i = 0; i < lhs.size(); ++i) v.at(i) *= rhs.at(i);
+        return zip_with(lhs, rhs, [](Int x, Int y) { return x * y; });
+    }
+    u8 operator-() const {
+        assert(!rank());
+        u8 v;
+        for (Int x : *this) v.push_back(wrap(-x));
         return v;
     }
+    friend u8 operator/(const u8 &lhs, const u8 &rhs) {
+        return zip_with(lhs, rhs, [](Int x, Int y) {
+            assert(wrap(y) != 0);
+            return HERANG_SIGNED ? Int(sext(x) == INT_MIN && sext(y) == -1 ? sext(x) : sext(x) / sext(y)) : x / y;
+        });
+    }
+    friend u8 operator%(const u8 &lhs, const u8 &rhs) {
+        return zip_with(lhs, rhs, [](Int x, Int y) {
+            assert(wrap(y) != 0);
+            return HERANG_SIGNED ? Int(sext(y) == -1 ? 0 : sext(x) % sext(y)) : x % y;
+        });
+    }
+    friend u8 operator&(const u8 &lhs, const u8 &rhs) {
+        return zip_with(lhs, rhs, [](Int x, Int y) { return x & y; });
+    }
+    friend u8 operator^(const u8 &lhs, const u8 &rhs) {
+        return zip_with(lhs, rhs, [](Int x, Int y) { return x ^ y; });
+    }
+    // Shifting by 32 or more gives 0, or fills with the sign bit for a
+    // signed `>>`, as in the interpreter.
+    friend u8 operator<<(const u8 &lhs, const u8 &rhs) {
+        return zip_with(lhs, rhs, [](Int x, Int y) { y = sext(y); return y < 32 ? Int(x << y) : Int(0); });
+    }
+    friend u8 operator>>(const u8 &lhs, const u8 &rhs) {
+        return zip_with(lhs, rhs, [](Int x, Int y) {
+            y = sext(y);
+            if (HERANG_SIGNED) return Int(sext(x) >> std::min(y, Int(31)));
+            return y < 32 ? Int(x >> y) : Int(0);
+        });
+    }
+
+    template <typename ...Args> u8 operator()(Args... args) const {
+        assert(func);
+        return (*func)(std::vector<u8>{u8(args)...});
+    }
 
     explicit operator bool() const {
-        return !empty() && std::all_of(begin(), end(), [](int x) {
+        if (is_list) return !items.empty();
+        if (entries) return !entries->empty();
+        return func || !empty() && std::all_of(begin(), end(), [](int x) {
             return bool(x);
         });
     }
+    // Numbers come before lists, then maps, then functions.
+    int rank() const { return func ? 3 : entries ? 2 : is_list ? 1 : 0; }
     friend bool less(const u8 &x, const u8 &y) {
-        return std::lexicographical_compare(x.begin(), x.end(), y.begin(), y.end());
+        if (x.is_list && y.is_list) {
+            return std::lexicographical_compare(x.items.begin(), x.items.end(), y.items.begin(), y.items.end(),
+                [](const u8 &a, const u8 &b) { return less(a, b); });
+        }
+        if (x.entries && y.entries) {
+            return std::lexicographical_compare(x.entries->begin(), x.entries->end(), y.entries->begin(), y.entries->end(),
+                [](const auto &a, const auto &b) {
+                    if (less(u8(a.first), u8(b.first))) return true;
+                    if (less(u8(b.first), u8(a.first))) return false;
+                    return less(a.second, b.second);
+                });
+        }
+        if (x.rank() || y.rank()) return x.rank() < y.rank();
+        return std::lexicographical_compare(x.begin(), x.end(), y.begin(), y.end(), [](Int a, Int b) {
+            return number(a) < number(b);
+        });
     }
     friend bool eq(const u8 &x, const u8 &y) {
+        if (x.rank() || y.rank()) return !less(x, y) && !less(y, x);
         return std::equal(x.begin(), x.end(), y.begin(), y.end());
     }
     friend u8 operator<(const u8 &x, const u8 &y) { return u8(Int(less(x, y))); }
@@ -65,7 +186,27 @@ public:
     friend u8 operator<=(const u8 &x, const u8 &y) { return u8(Int(!less(y, x))); }
     friend u8 operator>=(const u8 &x, const u8 &y) { return u8(Int(!less(x, y))); }
 
+    Map &own_entries() {
+        if (entries.use_count() > 1) entries = std::make_shared<Map>(*entries);
+        return *entries;
+    }
+
+    // A single index into a list gives that item, several give a list; the
+    // index of a map is a key.
     u8 operator[](const u8 &y) const {
+        if (entries) {
+            auto it = entries->find(y);
+            assert(it != entries->end());
+            return it->second;
+        }
+        if (is_list) {
+            std::vector<u8> z;
+            for (Int index : y) {
+                assert(index < items.size());
+                z.push_back(items.at(index));
+            }
+            return z.size() == 1 ? z.front() : make_list(z);
+        }
         u8 z;
         for (std::size_t i = 0; i < y.size(); ++i) {
             std::size_t index = y.at(i);
@@ -74,7 +215,27 @@ public:
         }
         return z;
     }
+    u8 &item(const u8 &y) {
+        if (entries) {
+            auto it = own_entries().find(y);
+            assert(it != entries->end());
+            return it->second;
+        }
+        assert(is_list && y.size() == 1 && y.at(0) < items.size());
+        return items.at(y.at(0));
+    }
     u8 &set(const u8 &y, const u8 &z) {
+        if (entries) {
+            own_entries()[y] = z;
+            return *this;
+        }
+        if (is_list) {
+            for (Int index : y) {
+                assert(index < items.size());
+                items.at(index) = z;
+            }
+            return *this;
+        }
         assert(!z.empty());
         for (std::size_t i = 0; i < y.size(); ++i) {
             std::size_t index = y.at(i);
@@ -85,10 +246,27 @@ public:
     }
 
     friend std::ostream &operator<<(std::ostream &out, const u8 &x) {
+        if (x.func) return out << "<function>";
+        if (x.is_list) {
+            out << "[";
+            for (std::size_t i = 0; i < x.items.size(); ++i) {
+                out << x.items.at(i);
+                if (i + 1 < x.items.size()) out << ", ";
+            }
+            return out << "]";
+        }
+        if (x.entries) {
+            out << "{";
+            for (auto it = x.entries->begin(); it != x.entries->end(); ++it) {
+                if (it != x.entries->begin()) out << ", ";
+                out << u8(it->first) << ": " << it->second;
+            }
+            return out << "}";
+        }
         out << "(";
         for (std::size_t i = 0; i < x.size(); ++i) {
-            std::cout << x.at(i);
-            if (i + 1 < x.size()) std::cout << " | ";
+            out << number(x.at(i));
+            if (i + 1 < x.size()) out << " | ";
         }
         return out << ")";
     }
@@ -96,11 +274,88 @@ public:
     Int sum() const { return std::accumulate(begin(), end(), Int()); }
 };
 
+u8 list(std::vector<u8> items) {
+    return u8::make_list(std::move(items));
+}
+
+u8 dict(std::vector<std::pair<u8, u8>> entries) {
+    u8 v;
+    v.entries = std::make_shared<Map>();
+    for (auto &entry : entries) v.set(entry.first, entry.second);
+    return v;
+}
+
+// What `@` iterates over: the items of a list, the keys of a map, or every
+// element on its own.
+std::vector<u8> elements(const u8 &a) {
+    if (a.is_list) return a.items;
+    if (a.entries) {
+        std::vector<u8> v;
+        for (auto &entry : *a.entries) v.push_back(u8(entry.first));
+        return v;
+    }
+    std::vector<u8> v;
+    for (Int x : a) v.push_back(u8(x));
+    return v;
+}
+
+// The number a single-element value stands for, as range bounds are.
+long long single(const u8 &a) {
+    assert(!a.rank() && a.size() == 1);
+    return number(a.at(0));
+}
+
+// Counts through `a..b step s`, or `a..=b step s` when `inclusive`, without
+// building the sequence; this is what `@` over a range lowers to.
+struct Range {
+    long long x, end, step;
+
+    Range(const u8 &a, const u8 &b, const u8 &s, bool inclusive) : x(single(a)), end(single(b)), step(single(s)) {
+        assert(step != 0);
+        if (inclusive) end += step > 0 ? 1 : -1;
+    }
+    explicit operator bool() const { return step > 0 ? x < end : x > end; }
+    u8 operator*() const { return u8(wrap(Int(x))); }
+    void operator++() { x += step; }
+};
+
+u8 range(const u8 &a, const u8 &b, const u8 &s, bool inclusive) {
+    u8 v;
+    for (Range r(a, b, s, inclusive); r; ++r) v.push_back(wrap(Int(r.x)));
+    return v;
+}
+
+// Wraps a `$` function, a lambda or a builtin into a function value.
+template <typename F, std::size_t ...I> u8 function(F f, std::index_sequence<I...>) {
+    u8 v;
+    v.func = std::make_shared<Function>([f](const std::vector<u8> &args) {
+        assert(args.size() == sizeof...(I));
+        return f(args.at(I)...);
+    });
+    return v;
+}
+template <typename ...Args> u8 function(std::function<u8(Args...)> f) {
+    return function(f, std::index_sequence_for<Args...>());
+}
+template <typename ...Args> u8 function(u8 (*f)(Args...)) {
+    return function(std::function<u8(Args...)>(f));
+}
+// A function imported from another file is already a value.
+u8 function(const u8 &f) {
+    return f;
+}
+
+// Thrown by `break` and `continue`, caught by the enclosing loop.
+struct Break {};
+struct Continue {};
+// Thrown by `return`, caught by the enclosing function.
+struct Return { u8 value; };
+
 u8 readline() {
     std::string s;
     std::getline(std::cin, s);
     u8 a;
-    for (auto x : s) a.push_back(x);
+    for (unsigned char x : s) a.push_back(x);
     return a;
 }
 
@@ -136,16 +391,396 @@ u8 trim(u8 a) {
     return a;
 }
 
+// The bytes of a value as text, and back.
+std::string text(const u8 &s) {
+    return std::string(s.begin(), s.end());
+}
+u8 bytes(const std::string &s) {
+    u8 v;
+    for (unsigned char c : s) v.push_back(c);
+    return v;
+}
+
+long long base_of(const u8 &base) {
+    long long b = single(base);
+    assert(2 <= b && b <= 36);
+    return b;
+}
+long long digit_of(Int c, long long base) {
+    long long d = isdigit(c) ? c - '0' : isalpha(c) ? tolower(c) - 'a' + 10 : 36;
+    assert(d < base);
+    return d;
+}
+char digit_char(long long d) {
+    return "0123456789abcdefghijklmnopqrstuvwxyz"[d];
+}
+bool big_endian(const u8 &order) {
+    assert(text(order) == "be" || text(order) == "le");
+    return text(order) == "be";
+}
+
+u8 parse_int(u8 s, u8 base) {
+    long long b = base_of(base);
+    bool negative = !s.empty() && s.front() == '-';
+    assert(s.size() > negative);
+    long long limit = HERANG_SIGNED ? (MASK >> 1) + negative : negative ? 0 : MASK;
+    long long n = 0;
+    for (std::size_t i = negative; i < s.size(); ++i) {
+        n = n * b + digit_of(s.at(i), b);
+        assert(n <= limit);
+    }
+    return u8({wrap(Int(negative ? -n : n))});
+}
+
+u8 parse_int(u8 s, u8 base, u8 order) {
+    long long b = base_of(base);
+    assert(!s.empty());
+    u8 v;
+    for (Int c : s) v.push_back(Int(digit_of(c, b)));
+    if (!big_endian(order)) std::reverse(v.begin(), v.end());
+    return v;
+}
+
+u8 to_str(u8 n, u8 base) {
+    long long b = base_of(base), x = single(n);
+    unsigned long long m = x < 0 ? -x : x;
+    std::string r;
+    do {
+        r += digit_char(m % b);
+        m /= b;
+    } while (m);
+    if (x < 0) r += '-';
+    std::reverse(r.begin(), r.end());
+    return bytes(r);
+}
+
+u8 to_str(u8 n, u8 base, u8 order) {
+    long long b = base_of(base);
+    std::string r;
+    for (Int x : n) {
+        assert(0 <= number(x) && number(x) < b);
+        r += digit_char(number(x));
+    }
+    if (!big_endian(order)) std::reverse(r.begin(), r.end());
+    if (r.empty()) r = "0";
+    return bytes(r);
+}
+
+template <typename ...Args> u8 fmt(u8 t, Args... args) {
+    std::vector<u8> values{u8(args)...};
+    std::string s = text(t);
+    std::ostringstream out;
+    std::size_t next = 0;
+    for (std::size_t i = 0; i < s.size(); ++i) {
+        if (s.compare(i, 2, "{}") != 0) {
+            out << s[i];
+            continue;
+        }
+        const u8 &x = values.at(next++);
+        if (!x.rank() && x.size() == 1) out << number(x.at(0));
+        else out << x;
+        ++i;
+    }
+    assert(next == values.size());
+    return bytes(out.str());
+}
+
+// A count or an index as a single element, which it must fit in.
+u8 count_of(std::size_t n) {
+    assert(n <= (MASK >> HERANG_SIGNED) && "length does not fit in an element");
+    return u8({Int(n)});
+}
+
 u8 len(u8 a) {
-    return u8({Int(a.size())});
+    return count_of(a.is_list ? a.items.size() : a.entries ? a.entries->size() : a.size());
 }
 
 u8 slice(u8 a, u8 l, u8 r) {
+    if (l.sum() >= r.sum()) return a.is_list ? list({}) : u8();
+    if (a.is_list) return list(std::vector<u8>(a.items.begin() + l.sum(), a.items.begin() + r.sum()));
     u8 b;
     b.assign(a.begin() + l.sum(), a.begin() + r.sum());
     return b;
 }
 
+// The items as a list when `s` is one, otherwise as numbers.
+u8 like(const u8 &s, const std::vector<u8> &items) {
+    if (s.is_list) return list(items);
+    u8 r;
+    for (const u8 &x : items) r.insert(r.end(), x.begin(), x.end());
+    return r;
+}
+
+struct ItemLess {
+    bool operator()(const u8 &x, const u8 &y) const { return less(x, y); }
+};
+
+// The items without repeats, keeping the first of each.
+std::vector<u8> distinct(const std::vector<u8> &items) {
+    std::set<u8, ItemLess> seen;
+    std::vector<u8> r;
+    for (const u8 &x : items) {
+        if (seen.insert(x).second) r.push_back(x);
+    }
+    return r;
+}
+
+u8 sort(u8 s) {
+    std::vector<u8> v = elements(s);
+    std::stable_sort(v.begin(), v.end(), ItemLess());
+    return like(s, v);
+}
+
+u8 sort_desc(u8 s) {
+    std::vector<u8> v = elements(s);
+    std::stable_sort(v.begin(), v.end(), [](const u8 &x, const u8 &y) { return less(y, x); });
+    return like(s, v);
+}
+
+u8 unique(u8 s) {
+    return like(s, distinct(elements(s)));
+}
+
+u8 binary_search(u8 s, u8 x) {
+    std::vector<u8> v = elements(s);
+    return count_of(std::lower_bound(v.begin(), v.end(), x, ItemLess()) - v.begin());
+}
+
+u8 min(u8 s) {
+    std::vector<u8> v = elements(s);
+    assert(!v.empty() && "empty sequence");
+    return *std::min_element(v.begin(), v.end(), ItemLess());
+}
+
+u8 max(u8 s) {
+    std::vector<u8> v = elements(s);
+    assert(!v.empty() && "empty sequence");
+    return *std::max_element(v.begin(), v.end(), ItemLess());
+}
+
+u8 sum(u8 s) {
+    Int t = 0;
+    for (const u8 &x : elements(s)) t += x.sum();
+    return u8({wrap(t)});
+}
+
+u8 count(u8 s, u8 x) {
+    std::vector<u8> v = elements(s);
+    return count_of(std::count_if(v.begin(), v.end(), [&](const u8 &y) { return eq(x, y); }));
+}
+
+u8 find(u8 f, u8 s) {
+    std::vector<u8> v = elements(s);
+    return count_of(std::find_if(v.begin(), v.end(), [&](const u8 &x) { return bool(f(x)); }) - v.begin());
+}
+
+// `union` is a C++ keyword, see `cpp_name`.
+u8 union_(u8 a, u8 b) {
+    std::vector<u8> v = elements(a), w = elements(b);
+    v.insert(v.end(), w.begin(), w.end());
+    return like(b.is_list ? b : a, distinct(v));
+}
+
+u8 intersect(u8 a, u8 b) {
+    std::vector<u8> v = elements(a), w = elements(b), r;
+    std::set<u8, ItemLess> in(w.begin(), w.end());
+    for (const u8 &x : v) {
+        if (in.count(x)) r.push_back(x);
+    }
+    return like(a, distinct(r));
+}
+
+u8 difference(u8 a, u8 b) {
+    std::vector<u8> v = elements(a), w = elements(b), r;
+    std::set<u8, ItemLess> in(w.begin(), w.end());
+    for (const u8 &x : v) {
+        if (!in.count(x)) r.push_back(x);
+    }
+    return like(a, distinct(r));
+}
+
+// Unsigned integers of any size for `bigadd` and the like, as little-endian
+// digits; results have no 0 past the most significant digit but have at
+// least one digit.
+using Big = std::vector<Int>;
+using Wide = unsigned long long;
+
+Wide big_base(const u8 &base) {
+    long long b = single(base);
+    assert(b == 0 || b >= 2);
+    return b == 0 ? Wide(MASK) + 1 : b;
+}
+Big big_digits(const u8 &a, Wide base) {
+    assert(!a.rank());
+    for (Int d : a) assert(d < base);
+    return Big(a.begin(), a.end());
+}
+Big big_trim(Big a) {
+    while (!a.empty() && a.back() == 0) a.pop_back();
+    if (a.empty()) a.push_back(0);
+    return a;
+}
+int big_cmp(const Big &x, const Big &y) {
+    Big a = big_trim(x), b = big_trim(y);
+    if (a.size() != b.size()) return a.size() < b.size() ? -1 : 1;
+    for (std::size_t i = a.size(); i-- > 0;) {
+        if (a[i] != b[i]) return a[i] < b[i] ? -1 : 1;
+    }
+    return 0;
+}
+Big big_add(const Big &a, const Big &b, Wide base) {
+    Big r;
+    Wide carry = 0;
+    for (std::size_t i = 0; i < std::max(a.size(), b.size()); ++i) {
+        Wide t = (i < a.size() ? a[i] : 0) + Wide(i < b.size() ? b[i] : 0) + carry;
+        r.push_back(Int(t % base));
+        carry = t / base;
+    }
+    r.push_back(Int(carry));
+    return big_trim(r);
+}
+Big big_sub(const Big &a, const Big &b, Wide base) {
+    assert(big_cmp(a, b) >= 0);
+    Big r;
+    Wide borrow = 0;
+    for (std::size_t i = 0; i < a.size(); ++i) {
+        Wide x = a[i], y = (i < b.size() ? b[i] : 0) + borrow;
+        borrow = x < y;
+        r.push_back(Int(x + borrow * base - y));
+    }
+    return big_trim(r);
+}
+Big big_mul(const Big &x, const Big &y, Wide base) {
+    Big a = big_trim(x), b = big_trim(y);
+    std::vector<Wide> r(a.size() + b.size() + 1);
+    for (std::size_t i = 0; i < a.size(); ++i) {
+        Wide carry = 0;
+        for (std::size_t j = 0; j < b.size(); ++j) {
+            Wide t = r[i + j] + Wide(a[i]) * b[j] + carry;
+            r[i + j] = t % base;
+            carry = t / base;
+        }
+        r[i + b.size()] = carry;
+    }
+    return big_trim(Big(r.begin(), r.end()));
+}
+std::pair<Big, Big> big_divmod(const Big &a, const Big &y, Wide base) {
+    Big b = big_trim(y);
+    assert(b.back() != 0);
+    Big q(a.size()), rest;
+    Wide top = b.back();
+    for (std::size_t i = a.size(); i-- > 0;) {
+        rest.insert(rest.begin(), a[i]);
+        rest = big_trim(rest);
+        Wide lead = 0;
+        if (rest.size() == b.size()) lead = rest.back();
+        if (rest.size() > b.size()) lead = rest.back() * base + rest[rest.size() - 2];
+        Wide low = lead / (top + 1), high = std::min(lead / top, base - 1);
+        while (low < high) {
+            Wide mid = (low + high + 1) / 2;
+            if (big_cmp(big_mul(b, {Int(mid)}, base), rest) > 0) high = mid - 1;
+            else low = mid;
+        }
+        q[i] = Int(low);
+        rest = big_sub(rest, big_mul(b, {Int(low)}, base), base);
+    }
+    return {big_trim(q), big_trim(rest)};
+}
+
+u8 bigadd(u8 a, u8 b, u8 base) {
+    Wide n = big_base(base);
+    return u8(big_add(big_digits(a, n), big_digits(b, n), n));
+}
+u8 bigsub(u8 a, u8 b, u8 base) {
+    Wide n = big_base(base);
+    return u8(big_sub(big_digits(a, n), big_digits(b, n), n));
+}
+u8 bigmul(u8 a, u8 b, u8 base) {
+    Wide n = big_base(base);
+    return u8(big_mul(big_digits(a, n), big_digits(b, n), n));
+}
+u8 bigdivmod(u8 a, u8 b, u8 base) {
+    Wide n = big_base(base);
+    auto r = big_divmod(big_digits(a, n), big_digits(b, n), n);
+    return list({u8(r.first), u8(r.second)});
+}
+u8 bigcmp(u8 a, u8 b, u8 base) {
+    Wide n = big_base(base);
+    return u8({Int(big_cmp(big_digits(a, n), big_digits(b, n)) + 1)});
+}
+u8 bigpow(u8 a, u8 e, u8 base) {
+    Wide n = big_base(base);
+    long long k = single(e);
+    assert(k >= 0);
+    Big r{1}, square = big_trim(big_digits(a, n));
+    for (; k > 0; k >>= 1) {
+        if (k & 1) r = big_mul(r, square, n);
+        if (k > 1) square = big_mul(square, square, n);
+    }
+    return u8(r);
+}
+u8 bigadd(u8 a, u8 b) { return bigadd(a, b, u8({10})); }
+u8 bigsub(u8 a, u8 b) { return bigsub(a, b, u8({10})); }
+u8 bigmul(u8 a, u8 b) { return bigmul(a, b, u8({10})); }
+u8 bigdivmod(u8 a, u8 b) { return bigdivmod(a, b, u8({10})); }
+u8 bigcmp(u8 a, u8 b) { return bigcmp(a, b, u8({10})); }
+u8 bigpow(u8 a, u8 e) { return bigpow(a, e, u8({10})); }
+
+u8 map(u8 f, u8 s) {
+    u8 r = s.is_list ? list({}) : u8();
+    for (const u8 &x : elements(s)) {
+        u8 y = f(x);
+        if (s.is_list) r.items.push_back(y);
+        else r.insert(r.end(), y.begin(), y.end());
+    }
+    return r;
+}
+
+u8 filter(u8 f, u8 s) {
+    u8 r = s.is_list ? list({}) : u8();
+    for (const u8 &x : elements(s)) {
+        if (!bool(f(x))) continue;
+        if (s.is_list) r.items.push_back(x);
+        else r.insert(r.end(), x.begin(), x.end());
+    }
+    return r;
+}
+
+u8 fold(u8 f, u8 init, u8 s) {
+    for (const u8 &x : elements(s)) init = f(init, x);
+    return init;
+}
+
+u8 get(u8 m, u8 key, u8 value) {
+    assert(m.entries);
+    auto it = m.entries->find(key);
+    return it == m.entries->end() ? value : it->second;
+}
+
+u8 set(u8 m, u8 key, u8 value) {
+    assert(m.entries);
+    return m.set(key, value);
+}
+
+u8 contains(u8 m, u8 key) {
+    if (m.entries) return u8(Int(m.entries->count(key)));
+    for (const u8 &x : elements(m)) {
+        if (eq(x, key)) return u8(Int(1));
+    }
+    return u8(Int(0));
+}
+
+u8 remove(u8 m, u8 key) {
+    assert(m.entries);
+    m.own_entries().erase(key);
+    return m;
+}
+
+u8 keys(u8 m) {
+    assert(m.entries);
+    return list(elements(m));
+}
+
 int main() {
     
     return 0;
@@ -1,11 +1,15 @@
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use dyn_clone::{clone_trait_object, DynClone};
 
 pub use crate::value::*;
+pub use crate::error::*;
 pub use crate::cpp_code::*;
 
-pub type HeResult = Result<Value, String>;
+pub type HeResult = Result<Value, HeError>;
 
 pub trait Func: fmt::Debug + DynClone {
     fn call(&self, args: &[Value], env: &mut HeEnv) -> HeResult;
@@ -15,27 +19,42 @@ clone_trait_object!(Func);
 
 #[derive(Debug, Clone)]
 pub struct UserFunc {
+    pub name: String,
     pub args_name: Vec<String>,
     pub body: Box<dyn AST>,
+    /// Where the function was defined, which is what its body sees.
+    pub scope: Scope,
 }
 
 impl UserFunc {
-    pub fn new(args_name: Vec<String>, body: Box<dyn AST>) -> Self {
-        Self { args_name, body }
+    pub fn new(name: String, args_name: Vec<String>, body: Box<dyn AST>, scope: Scope) -> Self {
+        Self { name, args_name, body, scope }
     }
 }
 
 impl Func for UserFunc {
     fn call(&self, args: &[Value], env: &mut HeEnv) -> HeResult {
         if args.len() != self.args_name.len() {
-            return Err(format!("Wrong number of arguments: expected {}, got {}", self.args_name.len(), args.len()));
+            return Err(HeError::ArityMismatch {
+                name: self.name.clone(),
+                expected: Arity::Exact(self.args_name.len()),
+                got: args.len(),
+                span: Span::default(),
+            });
         }
+        let caller = (!env.dynamic_scope()).then(|| env.replace_scope(self.scope.clone()));
         env.enter();
         for (arg, name) in args.iter().zip(self.args_name.iter()) {
             env.set_var_last(name.clone(), arg.clone())?;
         }
-        let result = self.body.eval(env);
+        let result = match self.body.eval(env) {
+            Err(HeError::Return { value, .. }) => Ok(value),
+            result => result.map_err(|e| e.with_trace(env.call_stack())),
+        };
         env.leave();
+        if let Some(caller) = caller {
+            env.replace_scope(caller);
+        }
         result
     }
 }
@@ -56,19 +75,116 @@ impl HeEnvLayer {
     }
 }
 
+/// The layers of variables and functions visible at some point, innermost
+/// last. Layers are shared, so a function sees later changes to the scope
+/// it was defined in.
+#[derive(Clone, Default)]
+pub struct Scope {
+    layers: Vec<Rc<RefCell<HeEnvLayer>>>,
+}
+
+/// Functions hold the scope they were defined in, which holds them again.
+impl fmt::Debug for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Scope({} layers)", self.layers.len())
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct HeEnv {
-    layers: Vec<HeEnvLayer>,
+    scope: Scope,
+    call_stack: Vec<Frame>,
+    numeric: Numeric,
+    dynamic_scope: bool,
+    /// The file being run, which imports are relative to.
+    file: Option<PathBuf>,
+    /// Imported files by their canonical path, each loaded once to run and
+    /// once to translate.
+    modules: HashMap<(PathBuf, bool), Rc<Module>>,
+    /// The files being imported, innermost last, to catch import cycles.
+    loading: Vec<PathBuf>,
+    /// The C++ of every imported file, in the order they were loaded.
+    module_code: Vec<String>,
+    prelude: bool,
+    prelude_entered: bool,
+}
+
+impl Default for HeEnv {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl HeEnv {
     pub fn new() -> Self {
-        HeEnv { layers: vec![HeEnvLayer::new()] }
+        let mut env = HeEnv {
+            scope: Scope::default(),
+            call_stack: Vec::new(),
+            numeric: Numeric::default(),
+            dynamic_scope: false,
+            file: None,
+            modules: HashMap::new(),
+            loading: Vec::new(),
+            module_code: Vec::new(),
+            prelude: true,
+            prelude_entered: false,
+        };
+        env.enter();
+        env
+    }
+
+    pub fn numeric(&self) -> Numeric {
+        self.numeric
+    }
+
+    pub fn set_numeric(&mut self, numeric: Numeric) {
+        self.numeric = numeric;
+    }
+
+    pub fn dynamic_scope(&self) -> bool {
+        self.dynamic_scope
+    }
+
+    /// Makes functions see the variables of their caller instead of the
+    /// ones where they were defined, as older versions did.
+    pub fn set_dynamic_scope(&mut self, dynamic_scope: bool) {
+        self.dynamic_scope = dynamic_scope;
+    }
+
+    pub fn prelude(&self) -> bool {
+        self.prelude
+    }
+
+    /// Whether files start with the functions of `src/prelude.her`.
+    pub fn set_prelude(&mut self, prelude: bool) {
+        self.prelude = prelude;
+    }
+
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    pub fn set_file(&mut self, file: PathBuf) {
+        self.file = Some(file);
+    }
+
+    /// What `gen_code` puts before the program, see `ImportAST`.
+    pub fn module_code(&self) -> &[String] {
+        &self.module_code
+    }
+
+    pub fn scope(&self) -> Scope {
+        self.scope.clone()
+    }
+
+    /// Switches to `scope`, returning the current one.
+    pub fn replace_scope(&mut self, scope: Scope) -> Scope {
+        std::mem::replace(&mut self.scope, scope)
     }
 
     pub fn get_var(&self, name: &str) -> Option<Value> {
-        for layer in self.layers.iter().rev() {
-            if let Some(value) = layer.vars.get(name) {
+        for layer in self.scope.layers.iter().rev() {
+            if let Some(value) = layer.borrow().vars.get(name) {
                 return Some(value.clone());
             }
         }
@@ -76,9 +192,9 @@ impl HeEnv {
     }
 
     pub fn set_var(&mut self, name: String, value: Value) -> HeResult {
-        for layer in self.layers.iter_mut().rev() {
-            if layer.vars.contains_key(&name) {
-                layer.vars.insert(name, value.clone());
+        for layer in self.scope.layers.iter().rev() {
+            if let Some(var) = layer.borrow_mut().vars.get_mut(&name) {
+                *var = value.clone();
                 return Ok(value);
             }
         }
@@ -86,13 +202,13 @@ impl HeEnv {
     }
 
     pub fn set_var_last(&mut self, name: String, value: Value) -> HeResult {
-        self.layers.last_mut().unwrap().vars.insert(name, value.clone());
+        self.last_layer().borrow_mut().vars.insert(name, value.clone());
         Ok(value)
     }
 
     pub fn get_func(&self, name: &str) -> Option<Box<dyn Func>> {
-        for layer in self.layers.iter().rev() {
-            if let Some(func) = layer.funcs.get(name) {
+        for layer in self.scope.layers.iter().rev() {
+            if let Some(func) = layer.borrow().funcs.get(name) {
                 return Some(func.clone());
             }
         }
@@ -100,25 +216,57 @@ impl HeEnv {
     }
 
     pub fn set_func(&mut self, name: String, func: Box<dyn Func>) -> HeResult {
-        if self.layers.last().unwrap().funcs.contains_key(&name) {
-            return Err(format!("Function {} already defined", name));
+        let mut layer = self.last_layer().borrow_mut();
+        if layer.funcs.contains_key(&name) {
+            return Err(HeError::FunctionRedefined { name, span: Span::default() });
         }
-        self.layers.last_mut().unwrap().funcs.insert(name, func);
+        layer.funcs.insert(name, func);
         Ok(Value::default())
     }
 
+    /// The functions defined in the innermost layer.
+    pub fn last_funcs(&self) -> BTreeMap<String, Box<dyn Func>> {
+        self.last_layer().borrow().funcs.clone().into_iter().collect()
+    }
+
+    fn last_layer(&self) -> &Rc<RefCell<HeEnvLayer>> {
+        self.scope.layers.last().unwrap()
+    }
+
     pub fn enter(&mut self) {
-        self.layers.push(HeEnvLayer::new());
+        self.scope.layers.push(Rc::new(RefCell::new(HeEnvLayer::new())));
     }
 
     pub fn leave(&mut self) {
-        self.layers.pop();
+        self.scope.layers.pop();
+    }
+
+    pub fn push_frame(&mut self, frame: Frame) {
+        self.call_stack.push(frame);
+    }
+
+    pub fn pop_frame(&mut self) {
+        self.call_stack.pop();
+    }
+
+    pub fn call_stack(&self) -> &[Frame] {
+        &self.call_stack
     }
 }
 
 pub trait AST: fmt::Debug + DynClone {
     fn eval(&self, env: &mut HeEnv) -> HeResult;
-    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), String>;
+    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), HeError>;
+
+    /// Lets `@` count through a range instead of building it.
+    fn as_range(&self) -> Option<&RangeAST> {
+        None
+    }
+
+    /// Whether the C++ is a declaration, which a block cannot `return`.
+    fn is_declaration(&self) -> bool {
+        false
+    }
 }
 
 clone_trait_object!(AST);
@@ -138,6 +286,12 @@ pub use if_cond::*;
 mod for_in;
 pub use for_in::*;
 
+mod while_loop;
+pub use while_loop::*;
+
+mod import;
+pub use import::*;
+
 #[derive(Debug, Clone)]
 pub struct BlockAST {
     statements: Vec<Box<dyn AST>>,
@@ -147,6 +301,15 @@ impl BlockAST {
     pub fn new(statements: Vec<Box<dyn AST>>) -> Self {
         BlockAST { statements }
     }
+
+    /// The statements one after another, without the lambda around them,
+    /// so what they define stays in the enclosing C++ scope.
+    pub fn gen_statements(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), HeError> {
+        for statement in self.statements.iter() {
+            statement.gen_code(env, code)?;
+        }
+        Ok(())
+    }
 }
 
 impl AST for BlockAST {
@@ -161,7 +324,7 @@ impl AST for BlockAST {
         }
     }
 
-    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), String> {
+    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), HeError> {
         code.push_line("[&]() {");
         code.enter();
 
@@ -171,10 +334,16 @@ impl AST for BlockAST {
             for i in 0..(self.statements.len()-1) {
                 self.statements[i].gen_code(env, code)?;
             }
-            code.push_line("return");
-            code.enter();
-            self.statements.last().unwrap().gen_code(env, code)?;
-            code.leave();
+            let last = self.statements.last().unwrap();
+            if last.is_declaration() {
+                last.gen_code(env, code)?;
+                code.push_line("return u8();");
+            } else {
+                code.push_line("return");
+                code.enter();
+                last.gen_code(env, code)?;
+                code.leave();
+            }
         }
 
         code.leave();
@@ -199,9 +368,13 @@ impl AST for StatementAST {
         self.statement.eval(env)
     }
 
-    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), String> {
+    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), HeError> {
         self.statement.gen_code(env, code)?;
         code.push_line(";");
         Ok(())
     }
+
+    fn is_declaration(&self) -> bool {
+        self.statement.is_declaration()
+    }
 }
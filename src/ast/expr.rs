@@ -1,61 +1,193 @@
 use super::*;
+use std::collections::BTreeMap;
+
+/// Operators work on numbers only, not on functions.
+pub(crate) fn expect_number(value: Value, span: Span) -> HeResult {
+    match value.object {
+        None => Ok(value),
+        Some(_) => Err(HeError::TypeMismatch { expected: "number", found: value.kind(), span }),
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct ValueAST {
     value: Value,
+    /// Whether it is a string or character, whose bytes are taken as they
+    /// are, negative for the signed ones.
+    is_text: bool,
+    span: Span,
 }
 
 impl ValueAST {
-    pub fn new(value: Value) -> Self {
-        ValueAST { value }
+    pub fn new(value: Value, span: Span) -> Self {
+        ValueAST { value, is_text: false, span }
+    }
+
+    pub fn text(value: Value, span: Span) -> Self {
+        ValueAST { value, is_text: true, span }
+    }
+
+    /// Number literals are rejected rather than truncated when they do not
+    /// fit.
+    fn check(&self, numeric: Numeric) -> Result<(), HeError> {
+        if self.is_text {
+            return Ok(());
+        }
+        match self.value.value.iter().find(|&&x| !numeric.fits(x)) {
+            Some(&value) => Err(HeError::LiteralOutOfRange { value, numeric, span: self.span }),
+            None => Ok(()),
+        }
     }
 }
 
 impl AST for ValueAST {
-    fn eval(&self, _env: &mut HeEnv) -> HeResult {
+    fn eval(&self, env: &mut HeEnv) -> HeResult {
+        self.check(env.numeric())?;
         Ok(self.value.clone())
     }
 
-    fn gen_code(&self, _env: &mut HeEnv, code: &mut CppCode) -> Result<(), String> {
+    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), HeError> {
+        self.check(env.numeric())?;
+        // `u8({})` would be ambiguous for the empty string `""`.
+        if self.value.value.is_empty() {
+            code.push_line("u8()");
+            return Ok(());
+        }
         let mut value = "".to_string();
-        if !self.value.value.is_empty() {
-            let mut first = true;
-            for v in self.value.value.iter() {
-                if !first {
-                    value += ", ";
-                } else {
-                    first = false;
-                }
-                value += v.to_string().as_str();
+        let mut first = true;
+        for v in self.value.value.iter() {
+            if !first {
+                value += ", ";
+            } else {
+                first = false;
             }
+            value += v.to_string().as_str();
         }
         code.push_line(format!("u8({{{}}})", value).as_str());
         Ok(())
     }
 }
 
+/// `[a, b, ...]`, a list of the values of its items.
+#[derive(Debug, Clone)]
+pub struct ListAST {
+    items: Vec<Box<dyn AST>>,
+}
+
+impl ListAST {
+    pub fn new(items: Vec<Box<dyn AST>>) -> Self {
+        ListAST { items }
+    }
+}
+
+impl AST for ListAST {
+    fn eval(&self, env: &mut HeEnv) -> HeResult {
+        let items = self.items.iter()
+            .map(|item| item.eval(env))
+            .collect::<Result<Vec<Value>, HeError>>()?;
+        Ok(Value::list(items))
+    }
+
+    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), HeError> {
+        code.push_line("list({");
+        code.enter();
+
+        let mut first = true;
+        for v in self.items.iter() {
+            if !first {
+                code.push_line(",");
+            } else {
+                first = false;
+            }
+            v.gen_code(env, code)?;
+        }
+
+        code.leave();
+        code.push_line("})");
+        Ok(())
+    }
+}
+
+/// `{key: value, ...}`, a map; a later entry replaces an earlier one with
+/// the same key.
+#[derive(Debug, Clone)]
+pub struct MapAST {
+    entries: Vec<(Box<dyn AST>, Box<dyn AST>)>,
+    span: Span,
+}
+
+impl MapAST {
+    pub fn new(entries: Vec<(Box<dyn AST>, Box<dyn AST>)>, span: Span) -> Self {
+        MapAST { entries, span }
+    }
+}
+
+impl AST for MapAST {
+    fn eval(&self, env: &mut HeEnv) -> HeResult {
+        let mut entries = BTreeMap::new();
+        for (key, value) in self.entries.iter() {
+            let key = expect_number(key.eval(env)?, self.span)?;
+            entries.insert(key.value, value.eval(env)?);
+        }
+        Ok(Value::map(entries))
+    }
+
+    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), HeError> {
+        code.push_line("dict({");
+        code.enter();
+
+        let mut first = true;
+        for (key, value) in self.entries.iter() {
+            if !first {
+                code.push_line(",");
+            } else {
+                first = false;
+            }
+            code.push_line("{");
+            key.gen_code(env, code)?;
+            code.push_line(",");
+            value.gen_code(env, code)?;
+            code.push_line("}");
+        }
+
+        code.leave();
+        code.push_line("})");
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct VarAST {
     var_name: String,
+    span: Span,
 }
 
 impl VarAST {
-    pub fn new(var_name: String) -> Self {
-        VarAST { var_name }
+    pub fn new(var_name: String, span: Span) -> Self {
+        VarAST { var_name, span }
+    }
+
+    fn not_found(&self) -> HeError {
+        HeError::UndefinedVariable { name: self.var_name.clone(), span: self.span }
     }
 }
 
+/// A name that is not a variable refers to the `$` function of that name.
 impl AST for VarAST {
     fn eval(&self, env: &mut HeEnv) -> HeResult {
         env.get_var(&self.var_name)
-            .ok_or(format!("Variable {} not found", self.var_name))
-            .map(|v| v.clone())
+            .or_else(|| env.get_func(&self.var_name).map(Value::func))
+            .ok_or_else(|| self.not_found())
     }
 
-    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), String> {
-        env.get_var(&self.var_name)
-            .ok_or(format!("Variable {} not found", self.var_name))?;
-        code.push_line(self.var_name.as_str());
+    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), HeError> {
+        if env.get_var(&self.var_name).is_some() {
+            code.push_line(cpp_var(&self.var_name).as_str());
+        } else if env.get_func(&self.var_name).is_some() {
+            code.push_line(format!("function({})", cpp_name(&self.var_name)).as_str());
+        } else {
+            return Err(self.not_found());
+        }
         Ok(())
     }
 }
@@ -76,7 +208,7 @@ impl AST for ExprAST {
         self.expr.eval(env)
     }
 
-    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), String> {
+    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), HeError> {
         self.expr.gen_code(env, code)
     }
 }
@@ -85,11 +217,12 @@ impl AST for ExprAST {
 pub struct OrExprAST {
     left: Box<dyn AST>,
     right: Box<dyn AST>,
+    span: Span,
 }
 
 impl OrExprAST {
-    pub fn new(left: Box<dyn AST>, right: Box<dyn AST>) -> Self {
-        OrExprAST { left, right }
+    pub fn new(left: Box<dyn AST>, right: Box<dyn AST>, span: Span) -> Self {
+        OrExprAST { left, right, span }
     }
 }
 
@@ -98,12 +231,12 @@ impl AST for OrExprAST {
         let left = self.left.eval(env)?;
         let right = self.right.eval(env)?;
 
-        let mut value = left.value.clone();
-        value.extend(right.value.clone());
-        Ok(Value::new(value))
+        let found = if left.object.is_some() { left.kind() } else { right.kind() };
+        left.concat(right)
+            .ok_or(HeError::TypeMismatch { expected: "number", found, span: self.span })
     }
 
-    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), String> {
+    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), HeError> {
         code.push_line("(");
 
         code.enter();
@@ -126,6 +259,28 @@ pub enum ArithmeticExprType {
     Add,
     Sub,
     Mul,
+    Div,
+    Rem,
+    BitAnd,
+    BitXor,
+    Shl,
+    Shr,
+}
+
+impl ArithmeticExprType {
+    pub fn sign(&self) -> &'static str {
+        match self {
+            ArithmeticExprType::Add => "+",
+            ArithmeticExprType::Sub => "-",
+            ArithmeticExprType::Mul => "*",
+            ArithmeticExprType::Div => "/",
+            ArithmeticExprType::Rem => "%",
+            ArithmeticExprType::BitAnd => "&",
+            ArithmeticExprType::BitXor => "^",
+            ArithmeticExprType::Shl => "<<",
+            ArithmeticExprType::Shr => ">>",
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -133,34 +288,48 @@ pub struct ArithmeticExprAST {
     left: Box<dyn AST>,
     right: Box<dyn AST>,
     expr_type: ArithmeticExprType,
+    span: Span,
 }
 
 impl ArithmeticExprAST {
-    pub fn new(left: Box<dyn AST>, right: Box<dyn AST>, expr_type: ArithmeticExprType) -> Self {
-        ArithmeticExprAST { left, right, expr_type }
+    pub fn new(left: Box<dyn AST>, right: Box<dyn AST>, expr_type: ArithmeticExprType, span: Span) -> Self {
+        ArithmeticExprAST { left, right, expr_type, span }
     }
 }
 
 impl AST for ArithmeticExprAST {
     fn eval(&self, env: &mut HeEnv) -> HeResult {
-        let left = self.left.eval(env)?;
-        let right = self.right.eval(env)?;
+        let numeric = env.numeric();
+        let left = numeric.extend(expect_number(self.left.eval(env)?, self.span)?);
+        let right = numeric.extend(expect_number(self.right.eval(env)?, self.span)?);
 
         let result = match self.expr_type {
             ArithmeticExprType::Add => left + right,
             ArithmeticExprType::Sub => left - right,
             ArithmeticExprType::Mul => left * right,
+            ArithmeticExprType::Div if numeric.is_signed() => left.signed_div(right),
+            ArithmeticExprType::Div => left / right,
+            ArithmeticExprType::Rem if numeric.is_signed() => left.signed_rem(right),
+            ArithmeticExprType::Rem => left % right,
+            ArithmeticExprType::BitAnd => left & right,
+            ArithmeticExprType::BitXor => left ^ right,
+            ArithmeticExprType::Shl => left << right,
+            ArithmeticExprType::Shr if numeric.is_signed() => left.signed_shr(right),
+            ArithmeticExprType::Shr => left >> right,
         };
 
-        Ok(result.into())
+        result
+            .map(|value| numeric.wrap(value))
+            .map_err(|e| match e {
+                ArithmeticError::DivisionByZero => HeError::DivisionByZero { span: self.span },
+                ArithmeticError::LengthMismatch { left, right } => {
+                    HeError::LengthMismatch { left, right, span: self.span }
+                }
+            })
     }
 
-    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), String> {
-        let sign = match self.expr_type {
-            ArithmeticExprType::Add => "+",
-            ArithmeticExprType::Sub => "-",
-            ArithmeticExprType::Mul => "*",
-        };
+    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), HeError> {
+        let sign = self.expr_type.sign();
         code.push_line("(");
 
         code.enter();
@@ -206,19 +375,20 @@ impl AST for EqualityExprAST {
         let left = self.left.eval(env)?;
         let right = self.right.eval(env)?;
 
+        let ordering = env.numeric().compare(&left, &right);
         let result = match self.expr_type {
-            EqualityExprType::Eq => left == right,
-            EqualityExprType::Ne => left != right,
-            EqualityExprType::Lt => left < right,
-            EqualityExprType::Gt => left > right,
-            EqualityExprType::Le => left <= right,
-            EqualityExprType::Ge => left >= right,
+            EqualityExprType::Eq => ordering.is_eq(),
+            EqualityExprType::Ne => ordering.is_ne(),
+            EqualityExprType::Lt => ordering.is_lt(),
+            EqualityExprType::Gt => ordering.is_gt(),
+            EqualityExprType::Le => ordering.is_le(),
+            EqualityExprType::Ge => ordering.is_ge(),
         };
 
         Ok(result.into())
     }
 
-    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), String> {
+    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), HeError> {
         let sign = match self.expr_type {
             EqualityExprType::Eq => "==",
             EqualityExprType::Ne => "!=",
@@ -243,3 +413,206 @@ impl AST for EqualityExprAST {
         Ok(())
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogicalExprType {
+    And,
+    Or,
+}
+
+/// `&&` and `||`, which only evaluate the right side when the left one does
+/// not decide the result.
+#[derive(Debug, Clone)]
+pub struct LogicalExprAST {
+    left: Box<dyn AST>,
+    right: Box<dyn AST>,
+    expr_type: LogicalExprType,
+}
+
+impl LogicalExprAST {
+    pub fn new(left: Box<dyn AST>, right: Box<dyn AST>, expr_type: LogicalExprType) -> Self {
+        LogicalExprAST { left, right, expr_type }
+    }
+}
+
+impl AST for LogicalExprAST {
+    fn eval(&self, env: &mut HeEnv) -> HeResult {
+        let left: bool = self.left.eval(env)?.into();
+        let result = match self.expr_type {
+            LogicalExprType::And => left && self.right.eval(env)?.into(),
+            LogicalExprType::Or => left || self.right.eval(env)?.into(),
+        };
+        Ok(result.into())
+    }
+
+    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), HeError> {
+        let sign = match self.expr_type {
+            LogicalExprType::And => "&&",
+            LogicalExprType::Or => "||",
+        };
+        code.push_line("u8(Int(bool(");
+
+        code.enter();
+        self.left.gen_code(env, code)?;
+        code.leave();
+
+        code.push_line(format!(") {} bool(", sign).as_str());
+
+        code.enter();
+        self.right.gen_code(env, code)?;
+        code.leave();
+
+        code.push_line(")))");
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NegExprAST {
+    expr: Box<dyn AST>,
+    span: Span,
+}
+
+impl NegExprAST {
+    pub fn new(expr: Box<dyn AST>, span: Span) -> Self {
+        NegExprAST { expr, span }
+    }
+}
+
+impl AST for NegExprAST {
+    fn eval(&self, env: &mut HeEnv) -> HeResult {
+        let value = expect_number(self.expr.eval(env)?, self.span)?;
+        Ok(env.numeric().wrap(-value))
+    }
+
+    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), HeError> {
+        code.push_line("(-");
+
+        code.enter();
+        self.expr.gen_code(env, code)?;
+        code.leave();
+
+        code.push_line(")");
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NotExprAST {
+    expr: Box<dyn AST>,
+}
+
+impl NotExprAST {
+    pub fn new(expr: Box<dyn AST>) -> Self {
+        NotExprAST { expr }
+    }
+}
+
+impl AST for NotExprAST {
+    fn eval(&self, env: &mut HeEnv) -> HeResult {
+        let value: bool = self.expr.eval(env)?.into();
+        Ok((!value).into())
+    }
+
+    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), HeError> {
+        code.push_line("u8(Int(!bool(");
+
+        code.enter();
+        self.expr.gen_code(env, code)?;
+        code.leave();
+
+        code.push_line(")))");
+        Ok(())
+    }
+}
+
+/// `start..end`, `start..=end` and `start..end step step`: the numbers from
+/// `start` up to `end`, or down to it for a negative step in signed mode.
+#[derive(Debug, Clone)]
+pub struct RangeAST {
+    start: Box<dyn AST>,
+    end: Box<dyn AST>,
+    step: Option<Box<dyn AST>>,
+    inclusive: bool,
+    span: Span,
+}
+
+impl RangeAST {
+    pub fn new(start: Box<dyn AST>, end: Box<dyn AST>, step: Option<Box<dyn AST>>, inclusive: bool, span: Span) -> Self {
+        RangeAST { start, end, step, inclusive, span }
+    }
+
+    fn bound(&self, name: &str, value: Value, env: &HeEnv) -> Result<i64, HeError> {
+        let value = expect_number(value, self.span)?;
+        match value.value[..] {
+            [x] => Ok(env.numeric().number(x)),
+            _ => Err(HeError::InvalidRange {
+                reason: format!("{} {} is not a single element", name, value),
+                span: self.span,
+            }),
+        }
+    }
+
+    /// The start, end and step, with the end made exclusive.
+    fn bounds(&self, env: &mut HeEnv) -> Result<(i64, i64, i64), HeError> {
+        let start = self.start.eval(env)?;
+        let start = self.bound("start", start, env)?;
+        let end = self.end.eval(env)?;
+        let end = self.bound("end", end, env)?;
+        let step = match &self.step {
+            Some(step) => {
+                let step = step.eval(env)?;
+                self.bound("step", step, env)?
+            }
+            None => 1,
+        };
+        if step == 0 {
+            return Err(HeError::InvalidRange { reason: "step is 0".to_string(), span: self.span });
+        }
+        let end = if self.inclusive { end + step.signum() } else { end };
+        Ok((start, end, step))
+    }
+
+    /// `start, end, step, inclusive`, the arguments of `range` and `Range`
+    /// in the C++ header.
+    pub fn gen_args(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), HeError> {
+        self.start.gen_code(env, code)?;
+        code.push_line(",");
+        self.end.gen_code(env, code)?;
+        code.push_line(",");
+        match &self.step {
+            Some(step) => step.gen_code(env, code)?,
+            None => code.push_line("u8({1})"),
+        }
+        code.push_line(format!(", {}", self.inclusive).as_str());
+        Ok(())
+    }
+}
+
+impl AST for RangeAST {
+    fn eval(&self, env: &mut HeEnv) -> HeResult {
+        let (start, end, step) = self.bounds(env)?;
+        let count = if step > 0 {
+            (end - start + step - 1).div_euclid(step)
+        } else {
+            (start - end - step - 1).div_euclid(-step)
+        };
+        let value = (0..count.max(0))
+            .map(|i| (start + i * step) as u32)
+            .collect();
+        Ok(env.numeric().wrap(Value::new(value)))
+    }
+
+    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), HeError> {
+        code.push_line("range(");
+        code.enter();
+        self.gen_args(env, code)?;
+        code.leave();
+        code.push_line(")");
+        Ok(())
+    }
+
+    fn as_range(&self) -> Option<&RangeAST> {
+        Some(self)
+    }
+}
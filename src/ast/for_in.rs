@@ -1,56 +1,81 @@
 use super::*;
 
+/// `@(x : value) {}`, with `x` set to each element of a number or each item
+/// of a list in turn.
 #[derive(Debug, Clone)]
 pub struct ForInAST {
     var_name: String,
     value: Box<dyn AST>,
     body: Box<dyn AST>,
+    span: Span,
 }
 
 impl ForInAST {
-    pub fn new(var_name: String, value: Box<dyn AST>, body: Box<dyn AST>) -> Self {
-        ForInAST { var_name, value, body }
+    pub fn new(var_name: String, value: Box<dyn AST>, body: Box<dyn AST>, span: Span) -> Self {
+        ForInAST { var_name, value, body, span }
     }
-}
 
-impl AST for ForInAST {
-    fn eval(&self, env: &mut HeEnv) -> HeResult {
-        let value = self.value.eval(env)?;
+    fn run(&self, value: Value, env: &mut HeEnv) -> Result<(), HeError> {
+        let items = value.items()
+            .ok_or(HeError::TypeMismatch { expected: "number or list", found: value.kind(), span: self.span })?;
         let mut first = true;
-
-        env.enter();
-        for v in value.clone().value {
-            let v = Value::new(vec![v]);
+        for v in items {
             if first {
-                env.set_var_last(self.var_name.clone(), v.clone()).unwrap();
+                env.set_var_last(self.var_name.clone(), v)?;
                 first = false;
             } else {
-                env.set_var(self.var_name.clone(), v.clone()).unwrap();
+                env.set_var(self.var_name.clone(), v)?;
+            }
+            if !eval_loop_body(self.body.as_ref(), env)? {
+                break;
             }
-            self.body.eval(env)?;
         }
+        Ok(())
+    }
+}
+
+impl AST for ForInAST {
+    fn eval(&self, env: &mut HeEnv) -> HeResult {
+        let value = self.value.eval(env)?;
+
+        env.enter();
+        let result = self.run(value, env);
         env.leave();
 
-        Ok(Value::default())
+        result.map(|_| Value::default())
     }
 
-    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), String> {
+    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), HeError> {
         code.push_line("[&]() {");
         code.enter();
 
         env.enter();
-        code.push_line(format!("for (Int _for_val_{} :", self.var_name).as_str());
+        // A range is counted through rather than built.
+        if let Some(range) = self.value.as_range() {
+            let name = format!("_for_range_{}", self.var_name);
+            code.push_line(format!("for (Range {}(", name).as_str());
 
-        code.enter();
-        self.value.gen_code(env, code)?;
-        code.leave();
+            code.enter();
+            range.gen_args(env, code)?;
+            code.leave();
 
-        code.push_line(") {");
-        code.enter();
-        code.push_line(format!("u8 {} = {{_for_val_{}}};", self.var_name, self.var_name).as_str());
+            code.push_line(format!("); {}; ++{}) {{", name, name).as_str());
+            code.enter();
+            code.push_var_def(&self.var_name, format!("*{}", name).as_str());
+        } else {
+            code.push_line(format!("for (const u8 &_for_val_{} : elements(", self.var_name).as_str());
+
+            code.enter();
+            self.value.gen_code(env, code)?;
+            code.leave();
+
+            code.push_line(")) {");
+            code.enter();
+            code.push_var_def(&self.var_name, format!("_for_val_{}", self.var_name).as_str());
+        }
         env.set_var_last(self.var_name.clone(), Value::default()).unwrap();
 
-        self.body.gen_code(env, code)?;
+        gen_loop_body(self.body.as_ref(), env, code)?;
         code.leave();
 
         code.push_line("}");
@@ -5,65 +5,129 @@ pub struct FuncDefAST {
     name: String,
     args: Vec<String>,
     body: Box<dyn AST>,
+    span: Span,
 }
 
 impl FuncDefAST {
-    pub fn new(name: String, args: Vec<String>, body: Box<dyn AST>) -> Self {
-        FuncDefAST { name, args, body }
+    pub fn new(name: String, args: Vec<String>, body: Box<dyn AST>, span: Span) -> Self {
+        FuncDefAST { name, args, body, span }
+    }
+
+    fn define(&self, env: &mut HeEnv) -> HeResult {
+        let func = UserFunc::new(self.name.clone(), self.args.clone(), self.body.clone(), env.scope());
+        env.set_func(self.name.clone(), Box::new(func))
+            .map_err(|e| e.or_span(self.span))
     }
 }
 
 impl AST for FuncDefAST {
     fn eval(&self, env: &mut HeEnv) -> HeResult {
-        let func = UserFunc::new(self.args.clone(), self.body.clone());
-        env.set_func(self.name.clone(), Box::new(func))
+        self.define(env)
     }
 
-    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), String> {
-        let func = UserFunc::new(self.args.clone(), self.body.clone());
-        env.set_func(self.name.clone(), Box::new(func))?;
-
-        let mut func_type = "std::function<u8(".to_string();
-        let mut first = true;
-        for _ in self.args.iter() {
-            if !first {
-                func_type += ", ";
-            } else {
-                first = false;
-            }
-            func_type += "u8";
-        }
-        func_type += ")>";
+    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), HeError> {
+        self.define(env)?;
 
-        env.enter();
-        code.push_line(format!("{} {} = [&](", func_type, self.name).as_str());
+        // A `static` function refers to itself without capturing.
+        let this = code.storage().is_empty().then(|| cpp_name(&self.name));
+        code.push_line(format!("{}{} {} =", code.storage(), func_type(&self.args), cpp_name(&self.name)).as_str());
         code.enter();
+        gen_lambda(&self.args, self.body.as_ref(), this.as_deref(), env, code)?;
+        code.leave();
+        code.push_line(";");
+        Ok(())
+    }
 
-        let mut first = true;
-        for v in self.args.iter() {
-            if !first {
-                code.push_line(",")
-            } else {
-                first = false;
-            }
-            code.push_line(format!("u8 {}", v).as_str());
-            env.set_var_last(v.clone(), Value::default())?;
+    fn is_declaration(&self) -> bool {
+        true
+    }
+}
+
+/// `std::function<u8(u8, ...)>` with one `u8` per argument.
+fn func_type(args: &[String]) -> String {
+    format!("std::function<u8({})>", vec!["u8"; args.len()].join(", "))
+}
+
+/// A C++ lambda taking `args` and running `body`. It captures by value so it
+/// can outlive the function that made it, which still shares variables as
+/// they are boxed, see `CppCode::push_var_def`. A `$` function also captures
+/// `this`, its own name, by reference so it can call itself.
+fn gen_lambda(args: &[String], body: &dyn AST, this: Option<&str>, env: &mut HeEnv, code: &mut CppCode) -> Result<(), HeError> {
+    env.enter();
+    match this {
+        Some(this) => code.push_line(format!("[=, &{}](", this).as_str()),
+        None => code.push_line("[=]("),
+    }
+    code.enter();
+
+    let mut first = true;
+    for v in args.iter() {
+        if !first {
+            code.push_line(",")
+        } else {
+            first = false;
         }
-        code.leave();
-        code.push_line(") {");
+        code.push_line(format!("u8 _arg_{}", v).as_str());
+        env.set_var_last(v.clone(), Value::default())?;
+    }
+    code.leave();
+    code.push_line(") {");
 
-        code.enter();
-        code.push_line("return");
+    code.enter();
+    for v in args.iter() {
+        code.push_var_def(v, format!("_arg_{}", v).as_str());
+    }
+    code.push_line("try {");
+    code.enter();
+    code.push_line("return");
+
+    code.enter();
+    let in_loop = std::mem::replace(&mut code.in_loop, false);
+    let in_func = std::mem::replace(&mut code.in_func, true);
+    body.gen_code(env, code)?;
+    code.in_loop = in_loop;
+    code.in_func = in_func;
+    code.leave();
+
+    code.leave();
+    code.push_line("} catch (Return &result) {");
+    code.enter();
+    code.push_line("return result.value;");
+    code.leave();
+    code.push_line("}");
+    code.leave();
+
+    code.push_line("}");
+    env.leave();
+
+    Ok(())
+}
 
-        code.enter();
-        self.body.gen_code(env, code)?;
-        code.leave();
+/// `$(x, y) { ... }`, a function without a name.
+#[derive(Debug, Clone)]
+pub struct LambdaAST {
+    args: Vec<String>,
+    body: Box<dyn AST>,
+}
 
-        code.leave();
-    
-        code.push_line("};");
-        env.leave();
+impl LambdaAST {
+    pub fn new(args: Vec<String>, body: Box<dyn AST>) -> Self {
+        LambdaAST { args, body }
+    }
+}
 
+impl AST for LambdaAST {
+    fn eval(&self, env: &mut HeEnv) -> HeResult {
+        let func = UserFunc::new("lambda".to_string(), self.args.clone(), self.body.clone(), env.scope());
+        Ok(Value::func(Box::new(func)))
+    }
+
+    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), HeError> {
+        code.push_line(format!("function({}(", func_type(&self.args)).as_str());
+        code.enter();
+        gen_lambda(&self.args, self.body.as_ref(), None, env, code)?;
+        code.leave();
+        code.push_line("))");
         Ok(())
     }
 }
@@ -72,33 +136,56 @@ impl AST for FuncDefAST {
 pub struct FuncCallAST {
     name: String,
     args: Vec<Box<dyn AST>>,
+    span: Span,
 }
 
 impl FuncCallAST {
-    pub fn new(name: String, args: Vec<Box<dyn AST>>) -> Self {
-        FuncCallAST { name, args }
+    pub fn new(name: String, args: Vec<Box<dyn AST>>, span: Span) -> Self {
+        FuncCallAST { name, args, span }
+    }
+
+    /// A variable holding a function comes before a `$` function of the
+    /// same name, so arguments can shadow functions.
+    fn get_func(&self, env: &HeEnv) -> Result<Box<dyn Func>, HeError> {
+        let var = env.get_var(&self.name);
+        if let Some(func) = var.as_ref().and_then(Value::as_func) {
+            return Ok(dyn_clone::clone_box(func));
+        }
+        match (env.get_func(&self.name), var) {
+            (Some(func), _) => Ok(func),
+            (None, Some(var)) => Err(HeError::TypeMismatch { expected: "function", found: var.kind(), span: self.span }),
+            (None, None) => Err(HeError::UndefinedFunction { name: self.name.clone(), span: self.span }),
+        }
     }
 }
 
 impl AST for FuncCallAST {
     fn eval(&self, env: &mut HeEnv) -> HeResult {
-        let func = env.get_func(&self.name)
-            .ok_or(format!("Function {} not found", self.name))?
-            .clone();
-        let args: Result<Vec<Value>, String> = self.args.iter()
+        let func = self.get_func(env)?;
+        let args: Result<Vec<Value>, HeError> = self.args.iter()
             .map(|arg| arg.eval(env))
             .collect();
 
         let args = args?;
-        func.call(&args, env)
+        env.push_frame(Frame::new(self.name.clone(), self.span));
+        let result = func.call(&args, env)
+            .map_err(|e| e.or_span(self.span));
+        env.pop_frame();
+        result
     }
 
-    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), String> {
+    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), HeError> {
         // TODO: check function's args count
-        env.get_func(&self.name)
-            .ok_or(format!("Function {} not found", self.name))?;
-        
-        code.push_line(format!("{}(", self.name).as_str());
+        // Arguments have no known value here, so any variable may hold a
+        // function.
+        if env.get_var(&self.name).is_none() {
+            self.get_func(env)?;
+        }
+
+        match env.get_var(&self.name) {
+            Some(_) => code.push_line(format!("{}(", cpp_var(&self.name)).as_str()),
+            None => code.push_line(format!("{}(", cpp_name(&self.name)).as_str()),
+        }
         code.enter();
 
         let mut first = true;
@@ -116,3 +203,42 @@ impl AST for FuncCallAST {
         Ok(())
     }
 }
+
+#[derive(Debug, Clone)]
+pub struct ReturnAST {
+    value: Option<Box<dyn AST>>,
+    span: Span,
+}
+
+impl ReturnAST {
+    pub fn new(value: Option<Box<dyn AST>>, span: Span) -> Self {
+        ReturnAST { value, span }
+    }
+}
+
+impl AST for ReturnAST {
+    fn eval(&self, env: &mut HeEnv) -> HeResult {
+        let value = match &self.value {
+            Some(value) => value.eval(env)?,
+            None => Value::default(),
+        };
+        Err(HeError::Return { value, span: self.span })
+    }
+
+    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), HeError> {
+        // Nothing would catch it outside of a function.
+        if !code.in_func {
+            return Err(HeError::Return { value: Value::default(), span: self.span });
+        }
+        // Blocks are lambdas, so unwind to the function with an exception.
+        code.push_line("(throw Return{");
+        code.enter();
+        match &self.value {
+            Some(value) => value.gen_code(env, code)?,
+            None => code.push_line("u8()"),
+        }
+        code.leave();
+        code.push_line("}, u8())");
+        Ok(())
+    }
+}
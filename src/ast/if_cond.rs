@@ -4,11 +4,12 @@ use super::*;
 pub struct IfAST {
     value: Box<dyn AST>,
     body: Box<dyn AST>,
+    else_body: Option<Box<dyn AST>>,
 }
 
 impl IfAST {
-    pub fn new(value: Box<dyn AST>, body: Box<dyn AST>) -> Self {
-        IfAST { value, body }
+    pub fn new(value: Box<dyn AST>, body: Box<dyn AST>, else_body: Option<Box<dyn AST>>) -> Self {
+        IfAST { value, body, else_body }
     }
 }
 
@@ -17,12 +18,14 @@ impl AST for IfAST {
         let value = self.value.eval(env)?;
         if value.into() {
             self.body.eval(env)
+        } else if let Some(else_body) = &self.else_body {
+            else_body.eval(env)
         } else {
             Ok(Value::default())
         }
     }
 
-    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), String> {
+    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), HeError> {
         code.push_line("[&]() {");
         code.enter();
 
@@ -32,14 +35,23 @@ impl AST for IfAST {
         code.leave();
         code.push_line(")) {");
 
-        code.push_line("return ");
+        code.enter();
+        code.push_line("return");
         code.enter();
         self.body.gen_code(env, code)?;
         code.leave();
+        code.leave();
 
         code.push_line("}");
 
-        code.push_line("return u8();");
+        if let Some(else_body) = &self.else_body {
+            code.push_line("return");
+            code.enter();
+            else_body.gen_code(env, code)?;
+            code.leave();
+        } else {
+            code.push_line("return u8();");
+        }
 
         code.leave();
         code.push_line("}();");
@@ -0,0 +1,222 @@
+use super::*;
+use std::fs;
+
+/// The `$` functions an imported file defines. `index` tells apart the C++
+/// names of different files.
+#[derive(Debug, Clone)]
+pub struct Module {
+    index: usize,
+    funcs: BTreeMap<String, Box<dyn Func>>,
+}
+
+impl Module {
+    /// Runs `source` in a scope of its own, as the file `file` whose
+    /// canonical path is `path`, and remembers it under that path.
+    fn load(env: &mut HeEnv, path: PathBuf, file: PathBuf, source: &str, gen_code: bool) -> Result<Rc<Module>, HeError> {
+        let block = crate::parse_block(source)?;
+        let file_name = file.display().to_string();
+
+        let mut code = CppCode::default();
+        code.enter();
+        code.static_depth = Some(code.tab_count);
+
+        let scope = env.replace_scope(Scope::default());
+        let file = env.file.replace(file);
+        env.loading.push(path.clone());
+        env.enter();
+        let result = crate::init_env(env).and_then(|_| {
+            if path != Path::new(PRELUDE_FILE) {
+                use_prelude(env, gen_code, &mut code)?;
+            }
+            env.enter();
+            if gen_code {
+                code.enter_layer();
+                block.gen_statements(env, &mut code)
+            } else {
+                block.eval(env).map(|_| ())
+            }
+        });
+        // Qualified imports inside the file are not passed on.
+        let funcs: BTreeMap<String, Box<dyn Func>> = env.last_funcs().into_iter()
+            .filter(|(name, _)| !name.contains("::"))
+            .collect();
+        env.loading.pop();
+        env.file = file;
+        env.replace_scope(scope);
+        result?;
+
+        let index = env.modules.len();
+        if gen_code {
+            let names: Vec<String> = funcs.keys().map(|name| format!("_import_{}_{}", index, name)).collect();
+            for (name, func) in names.iter().zip(funcs.keys()) {
+                code.push_line(format!("{} = function({});", name, cpp_name(func)).as_str());
+            }
+            code.leave_layer();
+            let mut module_code = CppCode::default();
+            module_code.push_line(format!("// {}", file_name).as_str());
+            if !names.is_empty() {
+                module_code.push_line(format!("u8 {};", names.join(", ")).as_str());
+            }
+            module_code.push_line("{");
+            module_code.push(&code.code);
+            module_code.push_line("}");
+            env.module_code.push(module_code.code);
+        }
+        let module = Rc::new(Module { index, funcs });
+        env.modules.insert((path, gen_code), module.clone());
+        Ok(module)
+    }
+
+    /// Makes the function `func` visible as `name` in the innermost layer,
+    /// in C++ as a reference to its copy.
+    fn bind(&self, env: &mut HeEnv, name: String, func: &str, code: &mut CppCode) -> Result<(), HeError> {
+        code.push_line(format!("{}u8 &{} = _import_{}_{};", code.storage(), cpp_name(&name), self.index, func).as_str());
+        env.set_func(name, self.funcs[func].clone())?;
+        Ok(())
+    }
+}
+
+/// What the prelude is loaded as; no file can be named like this.
+const PRELUDE_FILE: &str = "<prelude>";
+const PRELUDE: &str = include_str!("../prelude.her");
+
+/// Makes the functions of the prelude visible in the innermost layer,
+/// unless it is turned off.
+fn use_prelude(env: &mut HeEnv, gen_code: bool, code: &mut CppCode) -> Result<(), HeError> {
+    if !env.prelude() {
+        return Ok(());
+    }
+    let path = PathBuf::from(PRELUDE_FILE);
+    let module = match env.modules.get(&(path.clone(), gen_code)) {
+        Some(module) => module.clone(),
+        None => Module::load(env, path.clone(), path, PRELUDE, gen_code)?,
+    };
+    for func in module.funcs.keys() {
+        module.bind(env, func.clone(), func, code)?;
+    }
+    Ok(())
+}
+
+/// Puts the prelude in a layer of its own below the program the first time
+/// something is run in `env`, so the program can define the same names.
+pub fn enter_prelude(env: &mut HeEnv, gen_code: bool, code: &mut CppCode) -> Result<(), HeError> {
+    if env.prelude_entered {
+        return Ok(());
+    }
+    env.prelude_entered = true;
+    use_prelude(env, gen_code, code)?;
+    env.enter();
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImportKind {
+    /// `import "x.her";` or `import "x.her" as name;`: every function `f`
+    /// as `name::f`, where `name` defaults to the file name without `.her`.
+    Qualified(Option<String>),
+    /// `import f, g from "x.her";`: the functions listed, as they are.
+    Names(Vec<String>),
+}
+
+/// Runs another file, relative to the importing one, in a scope of its own
+/// and makes its `$` functions visible. A file is only run once, however
+/// often it is imported.
+///
+/// In C++ every imported file becomes a block before the program, in which
+/// its top-level definitions are `static` so its functions can still use
+/// them later. The functions are then copied out as `_import_<index>_<name>`
+/// function values, which imports refer to.
+#[derive(Debug, Clone)]
+pub struct ImportAST {
+    path: String,
+    kind: ImportKind,
+    span: Span,
+}
+
+impl ImportAST {
+    pub fn new(path: String, kind: ImportKind, span: Span) -> Self {
+        ImportAST { path, kind, span }
+    }
+
+    fn failed(&self, reason: String) -> HeError {
+        HeError::ImportFailed { path: self.path.clone(), reason, span: self.span }
+    }
+
+    /// An error inside the imported file, rendered against its own source.
+    fn failed_in(&self, error: HeError, file_name: &str, source: &str) -> HeError {
+        let message = crate::render(&error, file_name, source);
+        self.failed(message.strip_prefix("error: ").unwrap_or(&message).to_string())
+    }
+
+    fn load(&self, env: &mut HeEnv, gen_code: bool) -> Result<Rc<Module>, HeError> {
+        let dir = env.file().and_then(Path::parent).unwrap_or(Path::new(""));
+        let file = dir.join(&self.path);
+        let path = file.canonicalize().map_err(|e| self.failed(e.to_string()))?;
+        if let Some(module) = env.modules.get(&(path.clone(), gen_code)) {
+            return Ok(module.clone());
+        }
+        if let Some(start) = env.loading.iter().position(|file| *file == path) {
+            let cycle: Vec<String> = env.loading[start..].iter()
+                .chain([&path])
+                .map(|file| file.file_name().unwrap_or_default().to_string_lossy().to_string())
+                .collect();
+            return Err(self.failed(format!("import cycle {}", cycle.join(" -> "))));
+        }
+
+        let file_name = file.display().to_string();
+        let source = fs::read_to_string(&path).map_err(|e| self.failed(e.to_string()))?;
+        Module::load(env, path, file, &source, gen_code)
+            .map_err(|e| self.failed_in(e, &file_name, &source))
+    }
+
+    /// The names the functions of `module` are visible as, with the names
+    /// they have in `module`.
+    fn bindings(&self, module: &Module) -> Result<Vec<(String, String)>, HeError> {
+        match &self.kind {
+            ImportKind::Qualified(name) => {
+                let name = match name {
+                    Some(name) => name.clone(),
+                    None => self.file_stem()?,
+                };
+                Ok(module.funcs.keys().map(|func| (format!("{}::{}", name, func), func.clone())).collect())
+            }
+            ImportKind::Names(funcs) => funcs.iter()
+                .map(|func| match module.funcs.contains_key(func) {
+                    true => Ok((func.clone(), func.clone())),
+                    false => Err(self.failed(format!("it has no function {}", func))),
+                })
+                .collect(),
+        }
+    }
+
+    fn file_stem(&self) -> Result<String, HeError> {
+        let stem = Path::new(&self.path).file_stem().unwrap_or_default().to_string_lossy();
+        if stem.is_empty() || !stem.chars().all(|c| c.is_ascii_alphabetic() || c == '_') {
+            return Err(self.failed(format!("`{}` is not a name, add `as name`", stem)));
+        }
+        Ok(stem.to_string())
+    }
+
+    fn import(&self, env: &mut HeEnv, gen_code: bool, code: &mut CppCode) -> Result<(), HeError> {
+        let module = self.load(env, gen_code)?;
+        for (name, func) in self.bindings(&module)? {
+            module.bind(env, name, &func, code).map_err(|e| e.or_span(self.span))?;
+        }
+        Ok(())
+    }
+}
+
+impl AST for ImportAST {
+    fn eval(&self, env: &mut HeEnv) -> HeResult {
+        self.import(env, false, &mut CppCode::default())?;
+        Ok(Value::default())
+    }
+
+    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), HeError> {
+        self.import(env, true, code)
+    }
+
+    fn is_declaration(&self) -> bool {
+        true
+    }
+}
@@ -16,11 +16,15 @@ impl AST for VarDefAST {
         env.set_var_last(self.var_name.clone(), Value::default())
     }
 
-    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), String> {
+    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), HeError> {
         env.set_var_last(self.var_name.clone(), Value::default())?;
-        code.push_var_def(&self.var_name);
+        code.push_var_def(&self.var_name, "");
         Ok(())
     }
+
+    fn is_declaration(&self) -> bool {
+        true
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -41,13 +45,13 @@ impl AST for VarAssignAST {
         env.set_var(self.var_name.clone(), value.clone())
     }
 
-    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), String> {
+    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), HeError> {
         if env.get_var(&self.var_name).is_none() {
-            code.push_var_def(&self.var_name);
+            code.push_var_def(&self.var_name, "");
         }
         env.set_var(self.var_name.clone(), Value::default())?;
 
-        code.push_line(format!("({} = ", self.var_name).as_str());
+        code.push_line(format!("({} = ", cpp_var(&self.var_name)).as_str());
         code.enter();
         self.value.gen_code(env, code)?;
         code.leave();
@@ -56,52 +60,111 @@ impl AST for VarAssignAST {
     }
 }
 
+fn check_index(index: u32, len: usize, span: Span) -> Result<usize, HeError> {
+    let index = index as usize;
+    if index >= len {
+        return Err(HeError::IndexOutOfRange { index, len, span });
+    }
+    Ok(index)
+}
+
+/// `name[i][j]... = value`. Every index but the last one picks a single
+/// item of a list or an existing entry of a map; the last one works like
+/// `IndexAST`, replacing whole items of a list or elements of a number, or
+/// setting the entry of a map.
 #[derive(Debug, Clone)]
 pub struct VarRefAssignAST {
     var_name: String,
-    indexs: Box<dyn AST>,
+    indexs: Vec<Box<dyn AST>>,
     value: Box<dyn AST>,
+    span: Span,
 }
 
 impl VarRefAssignAST {
-    pub fn new(var_name: String, indexs: Box<dyn AST>, value: Box<dyn AST>) -> Self {
-        VarRefAssignAST { var_name, indexs, value }
+    pub fn new(var_name: String, indexs: Vec<Box<dyn AST>>, value: Box<dyn AST>, span: Span) -> Self {
+        VarRefAssignAST { var_name, indexs, value, span }
+    }
+
+    fn invalid(&self, reason: String) -> HeError {
+        HeError::InvalidAssignment { name: self.var_name.clone(), reason, span: self.span }
+    }
+
+    fn assign(&self, target: &mut Value, indexs: &[Value], value: Value) -> Result<(), HeError> {
+        let (index, rest) = indexs.split_first().unwrap();
+        let (len, found) = (target.len(), target.kind());
+        if let Some(items) = target.as_list_mut() {
+            if !rest.is_empty() {
+                if index.value.len() != 1 {
+                    return Err(self.invalid(format!("index {} of a nested list is not a single element", index)));
+                }
+                let i = check_index(index.value[0], len, self.span)?;
+                return self.assign(&mut items[i], rest, value);
+            }
+            for &i in index.value.iter() {
+                items[check_index(i, len, self.span)?] = value.clone();
+            }
+            return Ok(());
+        }
+        if let Some(entries) = target.as_map_mut() {
+            if rest.is_empty() {
+                entries.insert(index.value.clone(), value);
+                return Ok(());
+            }
+            let entry = entries.get_mut(&index.value)
+                .ok_or_else(|| HeError::KeyNotFound { key: index.clone(), span: self.span })?;
+            return self.assign(entry, rest, value);
+        }
+        if !rest.is_empty() || target.object.is_some() {
+            return Err(HeError::TypeMismatch { expected: "list", found, span: self.span });
+        }
+
+        let value = expect_number(value, self.span)?;
+        if value.value.is_empty() {
+            return Err(self.invalid("value is empty".to_string()));
+        }
+        if index.value.len() < value.value.len() {
+            return Err(self.invalid(format!("indexs {} are fewer than values {}", index, value)));
+        }
+        for i in 0..index.value.len() {
+            let index = check_index(index.value[i], len, self.span)?;
+            target.value[index] = value.value[i % value.value.len()];
+        }
+        Ok(())
     }
 }
 
 impl AST for VarRefAssignAST {
     fn eval(&self, env: &mut HeEnv) -> HeResult {
         let mut var = env.get_var(&self.var_name)
-            .ok_or(format!("Variable {} not found", self.var_name))?
-            .clone();
-        let indexs = self.indexs.eval(env)?;
+            .ok_or_else(|| HeError::UndefinedVariable { name: self.var_name.clone(), span: self.span })?;
+        let indexs = self.indexs.iter()
+            .map(|index| expect_number(index.eval(env)?, self.span))
+            .collect::<Result<Vec<Value>, HeError>>()?;
         let value = self.value.eval(env)?;
 
-        if value.value.is_empty() {
-            return Err(format!("Cannot assign empty value to variable {}", self.var_name));
-        }
-
-        if indexs.value.len() < value.value.len() {
-            return Err(format!("Cannot assign to variable {} with indexs {}", self.var_name, indexs));
-        }
-        for i in 0..indexs.value.len() {
-            let index = indexs.value[i] as usize;
-            if index >= var.value.len() {
-                return Err(format!("Cannot assign to variable {} with indexs {}", self.var_name, indexs));
-            }
-            var.value[index] = value.value[i % value.value.len()];
-        }
+        self.assign(&mut var, &indexs, value)?;
         env.set_var(self.var_name.clone(), var.clone())
     }
 
-    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), String> {
+    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), HeError> {
         env.get_var(&self.var_name)
-            .ok_or(format!("Variable {} not found", self.var_name))?;
+            .ok_or_else(|| HeError::UndefinedVariable { name: self.var_name.clone(), span: self.span })?;
         
-        code.push_line(format!("({}.set(", self.var_name).as_str());
+        code.push_line(format!("({}", cpp_var(&self.var_name)).as_str());
+
+        let (last, items) = self.indexs.split_last().unwrap();
+        for index in items {
+            code.push_line(".item(");
+            code.enter();
+            index.gen_code(env, code)?;
+            code.leave();
+            code.push_line(")");
+        }
+
+        code.push_line(".set(");
 
         code.enter();
-        self.indexs.gen_code(env, code)?;
+        last.gen_code(env, code)?;
         code.leave();
 
         code.push_line(",");
@@ -115,40 +178,58 @@ impl AST for VarRefAssignAST {
     }
 }
 
+/// `value[indexs]`. Indexing a number picks its elements at `indexs`;
+/// indexing a list with a single index gives that item, and with several a
+/// list of those items. Indexing a map gives the value of the key `indexs`.
 #[derive(Debug, Clone)]
-pub struct VarRefAST {
-    var_name: String,
+pub struct IndexAST {
+    value: Box<dyn AST>,
     indexs: Box<dyn AST>,
+    span: Span,
 }
 
-impl VarRefAST {
-    pub fn new(var_name: String, indexs: Box<dyn AST>) -> Self {
-        VarRefAST { var_name, indexs }
+impl IndexAST {
+    pub fn new(value: Box<dyn AST>, indexs: Box<dyn AST>, span: Span) -> Self {
+        IndexAST { value, indexs, span }
     }
 }
 
-impl AST for VarRefAST {
+impl AST for IndexAST {
     fn eval(&self, env: &mut HeEnv) -> HeResult {
-        let var = &env.get_var(&self.var_name)
-            .ok_or(format!("Variable {} not found", self.var_name))?;
-        let indexs = self.indexs.eval(env)?;
+        let value = self.value.eval(env)?;
+        let indexs = expect_number(self.indexs.eval(env)?, self.span)?;
+
+        if let Some(items) = value.as_list() {
+            let mut picked = indexs.value.iter()
+                .map(|&i| Ok(items[check_index(i, items.len(), self.span)?].clone()))
+                .collect::<Result<Vec<Value>, HeError>>()?;
+            return match picked.len() {
+                1 => Ok(picked.pop().unwrap()),
+                _ => Ok(Value::list(picked)),
+            };
+        }
+        if let Some(entries) = value.as_map() {
+            return entries.get(&indexs.value)
+                .cloned()
+                .ok_or(HeError::KeyNotFound { key: indexs, span: self.span });
+        }
+        let value = expect_number(value, self.span)?;
 
         let mut tmp_var = Value::default();
-        for i in 0..indexs.value.len() {
-            let index = indexs.value[i] as usize;
-            if index >= var.value.len() {
-                return Err(format!("Cannot get value from variable {} with indexs {}", self.var_name, indexs));
-            }
-            tmp_var.value.push(var.value[index])
+        for &i in indexs.value.iter() {
+            tmp_var.value.push(value.value[check_index(i, value.value.len(), self.span)?]);
         }
         Ok(tmp_var)
     }
 
-    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), String> {
-        env.get_var(&self.var_name)
-            .ok_or(format!("Variable {} not found", self.var_name))?;
-        
-        code.push_line(format!("{}[", self.var_name).as_str());
+    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), HeError> {
+        code.push_line("(");
+
+        code.enter();
+        self.value.gen_code(env, code)?;
+        code.leave();
+
+        code.push_line(")[");
 
         code.enter();
         self.indexs.gen_code(env, code)?;
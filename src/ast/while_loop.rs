@@ -0,0 +1,124 @@
+use super::*;
+
+/// Runs one iteration of a loop body. `Ok(false)` means the body hit `break`.
+pub(crate) fn eval_loop_body(body: &dyn AST, env: &mut HeEnv) -> Result<bool, HeError> {
+    match body.eval(env) {
+        Ok(_) | Err(HeError::Continue { .. }) => Ok(true),
+        Err(HeError::Break { .. }) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// `break` and `continue` are thrown as `Break` and `Continue` from
+/// `cpp_header.cpp`, since the body is a lambda and cannot use them directly.
+pub(crate) fn gen_loop_body(body: &dyn AST, env: &mut HeEnv, code: &mut CppCode) -> Result<(), HeError> {
+    code.push_line("try {");
+    code.enter();
+    let in_loop = std::mem::replace(&mut code.in_loop, true);
+    body.gen_code(env, code)?;
+    code.in_loop = in_loop;
+    code.leave();
+    code.push_line("} catch (Break &) {");
+    code.enter();
+    code.push_line("break;");
+    code.leave();
+    code.push_line("} catch (Continue &) {");
+    code.push_line("}");
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct WhileAST {
+    value: Box<dyn AST>,
+    body: Box<dyn AST>,
+}
+
+impl WhileAST {
+    pub fn new(value: Box<dyn AST>, body: Box<dyn AST>) -> Self {
+        WhileAST { value, body }
+    }
+}
+
+impl AST for WhileAST {
+    fn eval(&self, env: &mut HeEnv) -> HeResult {
+        while self.value.eval(env)?.into() {
+            if !eval_loop_body(self.body.as_ref(), env)? {
+                break;
+            }
+        }
+        Ok(Value::default())
+    }
+
+    fn gen_code(&self, env: &mut HeEnv, code: &mut CppCode) -> Result<(), HeError> {
+        code.push_line("[&]() {");
+        code.enter();
+
+        code.push_line("while (bool(");
+        code.enter();
+        self.value.gen_code(env, code)?;
+        code.leave();
+        code.push_line(")) {");
+
+        code.enter();
+        gen_loop_body(self.body.as_ref(), env, code)?;
+        code.leave();
+
+        code.push_line("}");
+        code.push_line("return u8();");
+
+        code.leave();
+        code.push_line("}()");
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BreakAST {
+    span: Span,
+}
+
+impl BreakAST {
+    pub fn new(span: Span) -> Self {
+        BreakAST { span }
+    }
+}
+
+impl AST for BreakAST {
+    fn eval(&self, _env: &mut HeEnv) -> HeResult {
+        Err(HeError::Break { span: self.span })
+    }
+
+    fn gen_code(&self, _env: &mut HeEnv, code: &mut CppCode) -> Result<(), HeError> {
+        // Thrown out of a function, it would end the caller's loop.
+        if !code.in_loop {
+            return Err(HeError::Break { span: self.span });
+        }
+        code.push_line("(throw Break(), u8())");
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ContinueAST {
+    span: Span,
+}
+
+impl ContinueAST {
+    pub fn new(span: Span) -> Self {
+        ContinueAST { span }
+    }
+}
+
+impl AST for ContinueAST {
+    fn eval(&self, _env: &mut HeEnv) -> HeResult {
+        Err(HeError::Continue { span: self.span })
+    }
+
+    fn gen_code(&self, _env: &mut HeEnv, code: &mut CppCode) -> Result<(), HeError> {
+        if !code.in_loop {
+            return Err(HeError::Continue { span: self.span });
+        }
+        code.push_line("(throw Continue(), u8())");
+        Ok(())
+    }
+}
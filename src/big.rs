@@ -0,0 +1,126 @@
+// Unsigned integers of any size for the `big*` builtins, as little-endian
+// digits in a base from 2 to 2^32. Digits past the most significant one
+// may be 0; results have none of them but always have at least one digit.
+
+use std::cmp::Ordering;
+
+/// `a` up to its most significant digit that is not 0.
+fn significant(a: &[u32]) -> &[u32] {
+    let len = a.iter().rposition(|&d| d != 0).map_or(0, |i| i + 1);
+    &a[..len]
+}
+
+fn trim(mut a: Vec<u32>) -> Vec<u32> {
+    a.truncate(significant(&a).len());
+    if a.is_empty() {
+        a.push(0);
+    }
+    a
+}
+
+pub fn cmp(a: &[u32], b: &[u32]) -> Ordering {
+    let (a, b) = (significant(a), significant(b));
+    a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+pub fn add(a: &[u32], b: &[u32], base: u64) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0;
+    for i in 0..a.len().max(b.len()) {
+        let sum = *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        result.push((sum % base) as u32);
+        carry = sum / base;
+    }
+    result.push(carry as u32);
+    trim(result)
+}
+
+/// `a - b`, or `None` when `b` is larger.
+pub fn sub(a: &[u32], b: &[u32], base: u64) -> Option<Vec<u32>> {
+    if cmp(a, b) == Ordering::Less {
+        return None;
+    }
+    let mut result = Vec::with_capacity(a.len());
+    let mut borrow = 0;
+    for (i, &x) in a.iter().enumerate() {
+        let y = *b.get(i).unwrap_or(&0) as u64 + borrow;
+        let (x, next) = if (x as u64) < y { (x as u64 + base, 1) } else { (x as u64, 0) };
+        result.push((x - y) as u32);
+        borrow = next;
+    }
+    Some(trim(result))
+}
+
+pub fn mul(a: &[u32], b: &[u32], base: u64) -> Vec<u32> {
+    let (a, b) = (significant(a), significant(b));
+    let mut result = vec![0u64; a.len() + b.len() + 1];
+    for (i, &x) in a.iter().enumerate() {
+        // At most (base - 1)^2 + 2 (base - 1), which fits in a `u64`.
+        let mut carry = 0;
+        for (j, &y) in b.iter().enumerate() {
+            let t = result[i + j] + x as u64 * y as u64 + carry;
+            result[i + j] = t % base;
+            carry = t / base;
+        }
+        result[i + b.len()] = carry;
+    }
+    trim(result.into_iter().map(|d| d as u32).collect())
+}
+
+/// `a / b` and `a % b`, or `None` when `b` is 0.
+pub fn divmod(a: &[u32], b: &[u32], base: u64) -> Option<(Vec<u32>, Vec<u32>)> {
+    let b = significant(b);
+    if b.is_empty() {
+        return None;
+    }
+    let mut quotient = vec![0; a.len()];
+    if let [d] = *b {
+        let mut rest = 0;
+        for (i, &x) in a.iter().enumerate().rev() {
+            let t = rest * base + x as u64;
+            quotient[i] = (t / d as u64) as u32;
+            rest = t % d as u64;
+        }
+        return Some((trim(quotient), vec![rest as u32]));
+    }
+    // Long division, finding every digit of the quotient by bisection
+    // between the bounds the leading digits give.
+    let top = *b.last().unwrap() as u64;
+    let mut rest: Vec<u32> = Vec::new();
+    for (i, &x) in a.iter().enumerate().rev() {
+        rest.insert(0, x);
+        let r = significant(&rest);
+        let lead = match r.len().checked_sub(b.len()) {
+            Some(0) => r[r.len() - 1] as u64,
+            Some(_) => r[r.len() - 1] as u64 * base + r[r.len() - 2] as u64,
+            None => 0,
+        };
+        let (mut low, mut high) = (lead / (top + 1), (lead / top).min(base - 1));
+        while low < high {
+            let mid = (low + high).div_ceil(2);
+            if cmp(&mul(b, &[mid as u32], base), &rest) == Ordering::Greater {
+                high = mid - 1;
+            } else {
+                low = mid;
+            }
+        }
+        quotient[i] = low as u32;
+        rest = sub(&rest, &mul(b, &[low as u32], base), base).unwrap();
+    }
+    Some((trim(quotient), trim(rest)))
+}
+
+pub fn pow(a: &[u32], mut exponent: u64, base: u64) -> Vec<u32> {
+    let mut result = vec![1];
+    let mut square = trim(a.to_vec());
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = mul(&result, &square, base);
+        }
+        exponent >>= 1;
+        if exponent > 0 {
+            square = mul(&square, &square, base);
+        }
+    }
+    result
+}
@@ -2,6 +2,14 @@
 pub struct CppCode {
     pub code: String,
     pub tab_count: usize,
+    /// Definitions at this depth are `static`, so they outlive the block
+    /// they are in; see `ImportAST`.
+    pub static_depth: Option<usize>,
+    /// Whether this is in a loop of the innermost function, where `break`
+    /// and `continue` can be thrown.
+    pub in_loop: bool,
+    /// Whether this is in a function, where `return` can be thrown.
+    pub in_func: bool,
 }
 
 impl CppCode {
@@ -33,14 +41,40 @@ impl CppCode {
         self.push("\n");
     }
 
-    pub fn push_var_def(&mut self, name: &str) {
-        self.push_line(format!("u8 {};", name).as_str());
+    /// Defines the variable `name` as `value`. Variables are boxed in a
+    /// `std::shared_ptr` that functions capture by value, so they share it
+    /// with where they were made and keep it after that ends; see `cpp_var`.
+    pub fn push_var_def(&mut self, name: &str, value: &str) {
+        self.push_line(format!("{}std::shared_ptr<u8> {} = std::make_shared<u8>({});", self.storage(), cpp_name(name), value).as_str());
+    }
+
+    /// Opens a block whose definitions are `static`, for a layer of names
+    /// that may hide the ones of the layer around it, as the prelude's.
+    pub fn enter_layer(&mut self) {
+        self.push_line("{");
+        self.enter();
+        self.static_depth = Some(self.tab_count);
+    }
+
+    pub fn leave_layer(&mut self) {
+        self.leave();
+        self.static_depth = Some(self.tab_count);
+        self.push_line("}");
+    }
+
+    /// `static ` for a definition at `static_depth`.
+    pub fn storage(&self) -> &'static str {
+        if self.static_depth == Some(self.tab_count) {
+            "static "
+        } else {
+            ""
+        }
     }
 }
 
 impl CppCode {
     pub fn new(code: String, tab_count: usize) -> Self {
-        Self { code, tab_count }
+        Self { code, tab_count, static_depth: None, in_loop: false, in_func: false }
     }
 }
 
@@ -49,3 +83,42 @@ impl Default for CppCode {
         Self::new(String::new(), 0usize)
     }
 }
+
+/// C++ keywords that are also valid names here.
+const CPP_KEYWORDS: &[&str] = &[
+    "alignas", "alignof", "and", "and_eq", "asm", "auto", "bitand", "bitor", "bool", "break",
+    "case", "catch", "char", "class", "compl", "const", "const_cast", "constexpr", "continue",
+    "decltype", "default", "delete", "do", "double", "dynamic_cast", "else", "enum", "explicit",
+    "export", "extern", "false", "float", "for", "friend", "goto", "if", "inline", "int", "long",
+    "mutable", "namespace", "new", "noexcept", "not", "not_eq", "nullptr", "operator", "or",
+    "or_eq", "private", "protected", "public", "register", "reinterpret_cast", "return", "short",
+    "signed", "sizeof", "static", "static_assert", "static_cast", "struct", "switch", "template",
+    "this", "throw", "true", "try", "typedef", "typeid", "typename", "union", "unsigned", "using",
+    "virtual", "void", "volatile", "while", "xor", "xor_eq",
+];
+
+/// What `cpp_header.cpp` defines besides the builtins, and what it uses from
+/// outside, so variables named like them do not hide them.
+const HEADER_NAMES: &[&str] = &[
+    "Big", "Break", "Continue", "Function", "HERANG_BITS", "HERANG_SIGNED", "Int", "ItemLess",
+    "MASK", "Map", "Range", "Return", "Wide", "assert", "base_of", "big_add", "big_base",
+    "big_cmp", "big_digits", "big_divmod", "big_endian", "big_mul", "big_sub", "big_trim",
+    "bytes", "count_of", "dict", "digit_char", "digit_of", "distinct", "elements", "eq",
+    "function", "less", "like", "list", "main", "number", "range", "sext", "single", "std",
+    "text", "wrap", "zip_with",
+];
+
+/// The C++ name of `name`, where a qualified `module::f` becomes
+/// `module__f` and a C++ keyword such as `union` or a name of the header
+/// such as `list` gets a trailing `_`.
+pub fn cpp_name(name: &str) -> String {
+    if CPP_KEYWORDS.contains(&name) || HEADER_NAMES.contains(&name) {
+        return format!("{}_", name);
+    }
+    name.replace("::", "__")
+}
+
+/// The C++ expression for the variable `name`, see `push_var_def`.
+pub fn cpp_var(name: &str) -> String {
+    format!("(*{})", cpp_name(name))
+}
@@ -1,26 +1,103 @@
-pub use crate::value::*;
 pub use crate::ast::*;
 
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::convert::TryFrom;
+use crate::big;
+
+fn check_arity(name: &str, expected: Arity, args: &[Value]) -> Result<(), HeError> {
+    if !expected.accepts(args.len()) {
+        return Err(HeError::ArityMismatch {
+            name: name.to_string(),
+            expected,
+            got: args.len(),
+            span: Span::default(),
+        });
+    }
+    Ok(())
+}
+
+/// The items of a list or the elements of a number, see `Value::items`.
+fn expect_items(value: &Value) -> Result<Vec<Value>, HeError> {
+    value.items()
+        .ok_or(HeError::TypeMismatch { expected: "number or list", found: value.kind(), span: Span::default() })
+}
+
+fn expect_map(value: &Value) -> Result<&BTreeMap<Vec<u32>, Value>, HeError> {
+    value.as_map()
+        .ok_or(HeError::TypeMismatch { expected: "map", found: value.kind(), span: Span::default() })
+}
+
+fn expect_key(value: &Value) -> Result<Vec<u32>, HeError> {
+    expect_number(value.clone(), Span::default()).map(|key| key.value)
+}
+
+/// `items` as a list when `like` is one, otherwise as numbers.
+fn collect_like(like: &Value, items: Vec<Value>) -> Value {
+    match like.as_list() {
+        Some(_) => Value::list(items),
+        None => Value::new(items.into_iter().flat_map(|x| x.value).collect()),
+    }
+}
+
+/// `items` without repeats, keeping the first of each.
+fn distinct(items: Vec<Value>, numeric: Numeric) -> Vec<Value> {
+    let mut seen: Vec<Value> = Vec::new();
+    items.into_iter()
+        .filter(|x| match seen.binary_search_by(|y| numeric.compare(y, x)) {
+            Ok(_) => false,
+            Err(i) => {
+                seen.insert(i, x.clone());
+                true
+            }
+        })
+        .collect()
+}
+
+/// `items` in the order `<` uses, to look items up with `has_item`.
+fn sorted(mut items: Vec<Value>, numeric: Numeric) -> Vec<Value> {
+    items.sort_by(|x, y| numeric.compare(x, y));
+    items
+}
+
+fn has_item(sorted: &[Value], x: &Value, numeric: Numeric) -> bool {
+    sorted.binary_search_by(|y| numeric.compare(y, x)).is_ok()
+}
+
+fn empty_sequence() -> HeError {
+    HeError::InvalidArgument { reason: "empty sequence".to_string(), span: Span::default() }
+}
+
+/// A count or an index as a single element, which it must fit in.
+fn count_value(count: usize, numeric: Numeric) -> HeResult {
+    if count as i64 > numeric.max() {
+        return Err(HeError::LengthOutOfRange { len: count, numeric, span: Span::default() });
+    }
+    Ok(Value::new(vec![count as u32]))
+}
+
+fn expect_func(value: &Value) -> Result<Box<dyn Func>, HeError> {
+    value.as_func()
+        .map(dyn_clone::clone_box)
+        .ok_or(HeError::TypeMismatch { expected: "function", found: value.kind(), span: Span::default() })
+}
 
 #[derive(Debug, Clone)]
 pub struct ReadLineFunc;
 
 impl Func for ReadLineFunc {
     fn call(&self, args: &[Value], _env: &mut HeEnv) -> HeResult {
-        if args.len() != 0 {
-            return Err(format!("readline requires 0 argument(got {})", args.len()).to_string());
-        }
+        check_arity("readline", Arity::Exact(0), args)?;
 
         let mut result = String::new();
     
         std::io::stdin()
             .read_line(&mut result)
-            .map_err(|e| e.to_string())?;
+            .map_err(|e| HeError::IoError { message: e.to_string(), span: Span::default() })?;
 
         Ok(Value::new(
             result.as_bytes()
-            .into_iter()
+            .iter()
             .map(|&x| x as u32)
             .collect()
         ))
@@ -31,13 +108,11 @@ impl Func for ReadLineFunc {
 pub struct PrintFunc;
 
 impl Func for PrintFunc {
-    fn call(&self, args: &[Value], _env: &mut HeEnv) -> HeResult {
-        if args.is_empty() {
-            return Err("print requires at least 1 argument".to_string());
-        }
+    fn call(&self, args: &[Value], env: &mut HeEnv) -> HeResult {
+        check_arity("print", Arity::AtLeast(1), args)?;
 
         let message = args.iter()
-            .map(|arg| format!("{}", arg))
+            .map(|arg| env.numeric().format(arg))
             .collect::<Vec<String>>()
             .join(" ");
         println!("{}", message);
@@ -50,21 +125,19 @@ impl Func for PrintFunc {
 pub struct SPrintFunc;
 
 impl SPrintFunc {
-    fn sprint(value: &Value) -> String {
-        let value = value.value.clone();
-        String::from_utf8(value.iter().map(|&x| x as u8).collect()).unwrap()
+    fn sprint(value: &Value) -> Result<String, HeError> {
+        String::from_utf8(value.value.iter().map(|&x| x as u8).collect())
+            .map_err(|_| HeError::InvalidUtf8 { span: Span::default() })
     }
 }
 
 impl Func for SPrintFunc {
     fn call(&self, args: &[Value], _env: &mut HeEnv) -> HeResult {
-        if args.is_empty() {
-            return Err("sprint requires at least 1 argument".to_string());
-        }
+        check_arity("sprint", Arity::AtLeast(1), args)?;
 
         let message = args.iter()
             .map(SPrintFunc::sprint)
-            .collect::<Vec<String>>()
+            .collect::<Result<Vec<String>, HeError>>()?
             .join(" ");
         println!("{}", message);
 
@@ -77,14 +150,10 @@ pub struct CyberFunc;
 
 impl Func for CyberFunc {
     fn call(&self, args: &[Value], _env: &mut HeEnv) -> HeResult {
-        if args.len() != 1 {
-            return Err(format!("cyber requires 1 argument(got {})", args.len()));
-        }
+        check_arity("cyber", Arity::Exact(1), args)?;
 
-        let count = args[0].value.iter().fold(0, |acc, &v| acc + v);
-        let mut value = Vec::new();
-        value.resize(count as usize, 0);
-        Ok(Value::new(value))
+        let count = args[0].value.iter().sum::<u32>();
+        Ok(Value::new(vec![0; count as usize]))
     }
 }
 
@@ -93,18 +162,16 @@ pub struct TrimFunc;
 
 impl Func for TrimFunc {
     fn call(&self, args: &[Value], _env: &mut HeEnv) -> HeResult {
-        if args.len() != 1 {
-            return Err(format!("trim requires 1 argument(got {})", args.len()));
-        }
+        check_arity("trim", Arity::Exact(1), args)?;
 
         let value = args[0].value.clone();
         let value = String::from_utf8(
                 value.iter().map(|&x| x as u8).collect()
             )
-            .map_err(|e| e.to_string())?
+            .map_err(|_| HeError::InvalidUtf8 { span: Span::default() })?
             .trim()
             .as_bytes()
-            .into_iter()
+            .iter()
             .map(|&x| x as u32)
             .collect();
         
@@ -112,16 +179,190 @@ impl Func for TrimFunc {
     }
 }
 
+fn invalid_number(reason: String) -> HeError {
+    HeError::InvalidNumber { reason, span: Span::default() }
+}
+
+/// The text a value holds, for error messages.
+fn text(value: &Value) -> String {
+    String::from_utf8_lossy(&value.value.iter().map(|&x| x as u8).collect::<Vec<u8>>()).to_string()
+}
+
+/// The base of `parse_int` and `to_str`, from 2 to 36.
+fn expect_base(value: &Value, numeric: Numeric) -> Result<u32, HeError> {
+    let base = expect_number(value.clone(), Span::default())?;
+    match base.value[..] {
+        [x] if (2..=36).contains(&numeric.number(x)) => Ok(numeric.number(x) as u32),
+        _ => Err(invalid_number(format!("base {} is not from 2 to 36", numeric.format(&base)))),
+    }
+}
+
+/// Whether a digit array is big-endian, given as `"be"` or `"le"`.
+fn expect_order(value: &Value) -> Result<bool, HeError> {
+    match text(value).as_str() {
+        "be" => Ok(true),
+        "le" => Ok(false),
+        _ => Err(invalid_number(format!("the order is \"{}\" instead of \"be\" or \"le\"", text(value)))),
+    }
+}
+
+fn digit_value(c: u32, base: u32) -> Result<u32, HeError> {
+    char::from_u32(c)
+        .and_then(|c| c.to_digit(base))
+        .ok_or_else(|| match char::from_u32(c) {
+            Some(c) if c.is_ascii_graphic() => invalid_number(format!("`{}` is not a digit in base {}", c, base)),
+            _ => invalid_number(format!("byte {} is not a digit in base {}", c, base)),
+        })
+}
+
+fn digit_char(digit: u32, base: u32) -> u32 {
+    char::from_digit(digit, base).unwrap() as u32
+}
+
+/// `parse_int(s, base)`: the number the ASCII digits `s` stand for, with a
+/// leading `-` for negative numbers; it has to fit in an element.
+///
+/// `parse_int(s, base, order)`: the value of every digit on its own, most
+/// significant first when `order` is `"be"` and last when it is `"le"`, for
+/// numbers of any size.
+#[derive(Debug, Clone)]
+pub struct ParseIntFunc;
+
+impl Func for ParseIntFunc {
+    fn call(&self, args: &[Value], env: &mut HeEnv) -> HeResult {
+        check_arity("parse_int", Arity::Between(2, 3), args)?;
+
+        let numeric = env.numeric();
+        let s = expect_number(args[0].clone(), Span::default())?;
+        let base = expect_base(&args[1], numeric)?;
+        if let Some(order) = args.get(2) {
+            let mut digits = s.value.iter()
+                .map(|&c| digit_value(c, base))
+                .collect::<Result<Vec<u32>, HeError>>()?;
+            if digits.is_empty() {
+                return Err(invalid_number("there are no digits".to_string()));
+            }
+            if !expect_order(order)? {
+                digits.reverse();
+            }
+            return Ok(Value::new(digits));
+        }
+
+        let (negative, digits) = match s.value.split_first() {
+            Some((&c, rest)) if c == '-' as u32 => (true, rest),
+            _ => (false, &s.value[..]),
+        };
+        if digits.is_empty() {
+            return Err(invalid_number("there are no digits".to_string()));
+        }
+        let limit = if negative { -numeric.min() } else { numeric.max() };
+        let mut n: i64 = 0;
+        for &c in digits {
+            n = n * base as i64 + digit_value(c, base)? as i64;
+            if n > limit {
+                return Err(invalid_number(format!("`{}` does not fit in {}", text(&s), numeric)));
+            }
+        }
+        let n = if negative { -n } else { n };
+        Ok(numeric.wrap(Value::new(vec![n as u32])))
+    }
+}
+
+/// `to_str(n, base)`: the ASCII digits of the single element `n`, with a
+/// leading `-` for negative numbers.
+///
+/// `to_str(n, base, order)`: the digits of a digit array as `parse_int`
+/// gives it, which is `"0"` when there are none.
+#[derive(Debug, Clone)]
+pub struct ToStrFunc;
+
+impl Func for ToStrFunc {
+    fn call(&self, args: &[Value], env: &mut HeEnv) -> HeResult {
+        check_arity("to_str", Arity::Between(2, 3), args)?;
+
+        let numeric = env.numeric();
+        let n = expect_number(args[0].clone(), Span::default())?;
+        let base = expect_base(&args[1], numeric)?;
+        let mut digits = Vec::new();
+        if let Some(order) = args.get(2) {
+            for &x in n.value.iter() {
+                match u32::try_from(numeric.number(x)) {
+                    Ok(digit) if digit < base => digits.push(digit_char(digit, base)),
+                    _ => return Err(invalid_number(format!("{} is not a digit in base {}", numeric.number(x), base))),
+                }
+            }
+            if expect_order(order)? {
+                digits.reverse();
+            }
+            if digits.is_empty() {
+                digits.push('0' as u32);
+            }
+        } else {
+            let x = match n.value[..] {
+                [x] => numeric.number(x),
+                _ => return Err(invalid_number(format!("{} is not a single element", numeric.format(&n)))),
+            };
+            let mut m = x.unsigned_abs();
+            loop {
+                digits.push(digit_char((m % base as u64) as u32, base));
+                m /= base as u64;
+                if m == 0 {
+                    break;
+                }
+            }
+            if x < 0 {
+                digits.push('-' as u32);
+            }
+        }
+        digits.reverse();
+        Ok(Value::new(digits))
+    }
+}
+
+/// `fmt(template, ...)`: `template` with every `{}` replaced by the next
+/// argument, a single element as its digits and anything else as `print`
+/// shows it.
+#[derive(Debug, Clone)]
+pub struct FmtFunc;
+
+impl Func for FmtFunc {
+    fn call(&self, args: &[Value], env: &mut HeEnv) -> HeResult {
+        check_arity("fmt", Arity::AtLeast(1), args)?;
+
+        let numeric = env.numeric();
+        let template = expect_number(args[0].clone(), Span::default())?.value;
+        let placeholders = template.windows(2).filter(|pair| *pair == ['{' as u32, '}' as u32]).count();
+        check_arity("fmt", Arity::Exact(placeholders + 1), args)?;
+
+        let mut result = Vec::new();
+        let mut values = args[1..].iter();
+        let mut i = 0;
+        while i < template.len() {
+            if template[i..].starts_with(&['{' as u32, '}' as u32]) {
+                let value = values.next().unwrap();
+                let shown = match (&value.object, &value.value[..]) {
+                    (None, [x]) => numeric.number(*x).to_string(),
+                    _ => numeric.format(value),
+                };
+                result.extend(shown.bytes().map(u32::from));
+                i += 2;
+            } else {
+                result.push(template[i]);
+                i += 1;
+            }
+        }
+        Ok(Value::new(result))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct LenFunc;
 
 impl Func for LenFunc {
-    fn call(&self, args: &[Value], _env: &mut HeEnv) -> HeResult {
-        if args.len() != 1 {
-            return Err(format!("len requires 1 argument(got {})", args.len()));
-        }
+    fn call(&self, args: &[Value], env: &mut HeEnv) -> HeResult {
+        check_arity("len", Arity::Exact(1), args)?;
 
-        Ok(Value::new(vec![args[0].value.len() as u32]))
+        count_value(args[0].len(), env.numeric())
     }
 }
 
@@ -130,23 +371,461 @@ pub struct SliceFunc;
 
 impl Func for SliceFunc {
     fn call(&self, args: &[Value], _env: &mut HeEnv) -> HeResult {
-        if args.len() != 3 {
-            return Err(format!("len requires 1 argument(got {})", args.len()));
-        }
+        check_arity("slice", Arity::Exact(3), args)?;
 
-        let value = &args[0].value;
+        let len = args[0].len();
         let start = usize::try_from(
-            args[1].value.iter().fold(0u32, |acc, &v| acc + v)
+            args[1].value.iter().sum::<u32>()
         ).unwrap();
         let end = usize::try_from(
-            args[2].value.iter().fold(0u32, |acc, &v| acc + v)
+            args[2].value.iter().sum::<u32>()
         ).unwrap();
         if start >= end {
-            return Ok(Value::new(vec![]));
+            return Ok(match args[0].as_list() {
+                Some(_) => Value::list(vec![]),
+                None => Value::new(vec![]),
+            });
+        }
+        if end > len {
+            return Err(HeError::IndexOutOfRange { index: end, len, span: Span::default() });
         }
-        if end > value.len() {
-            return Err(format!("out of range(got [{}, {}])", start, end));
+        match args[0].as_list() {
+            Some(items) => Ok(Value::list(items[start..end].to_vec())),
+            None => Ok(Value::new(args[0].value[start..end].to_vec())),
         }
-        Ok(Value::new((&value[start..end]).to_vec()))
+    }
+}
+
+/// `sort(s)`: the items of `s` from the smallest to the largest, in the
+/// order `<` uses.
+#[derive(Debug, Clone)]
+pub struct SortFunc;
+
+impl Func for SortFunc {
+    fn call(&self, args: &[Value], env: &mut HeEnv) -> HeResult {
+        check_arity("sort", Arity::Exact(1), args)?;
+
+        let items = sorted(expect_items(&args[0])?, env.numeric());
+        Ok(collect_like(&args[0], items))
+    }
+}
+
+/// `sort_desc(s)`: the items of `s` from the largest to the smallest.
+#[derive(Debug, Clone)]
+pub struct SortDescFunc;
+
+impl Func for SortDescFunc {
+    fn call(&self, args: &[Value], env: &mut HeEnv) -> HeResult {
+        check_arity("sort_desc", Arity::Exact(1), args)?;
+
+        let numeric = env.numeric();
+        let mut items = expect_items(&args[0])?;
+        items.sort_by(|x, y| numeric.compare(y, x));
+        Ok(collect_like(&args[0], items))
+    }
+}
+
+/// `unique(s)`: the items of `s` without repeats, keeping the first of each.
+#[derive(Debug, Clone)]
+pub struct UniqueFunc;
+
+impl Func for UniqueFunc {
+    fn call(&self, args: &[Value], env: &mut HeEnv) -> HeResult {
+        check_arity("unique", Arity::Exact(1), args)?;
+
+        let items = distinct(expect_items(&args[0])?, env.numeric());
+        Ok(collect_like(&args[0], items))
+    }
+}
+
+/// `binary_search(s, x)`: the index of the first item of the sorted `s`
+/// that is not less than `x`, which is `len(s)` when there is none.
+#[derive(Debug, Clone)]
+pub struct BinarySearchFunc;
+
+impl Func for BinarySearchFunc {
+    fn call(&self, args: &[Value], env: &mut HeEnv) -> HeResult {
+        check_arity("binary_search", Arity::Exact(2), args)?;
+
+        let numeric = env.numeric();
+        let items = expect_items(&args[0])?;
+        let index = items.partition_point(|x| numeric.compare(x, &args[1]) == Ordering::Less);
+        count_value(index, numeric)
+    }
+}
+
+/// `min(s)`: the smallest item of `s`, which must not be empty.
+#[derive(Debug, Clone)]
+pub struct MinFunc;
+
+impl Func for MinFunc {
+    fn call(&self, args: &[Value], env: &mut HeEnv) -> HeResult {
+        check_arity("min", Arity::Exact(1), args)?;
+
+        let numeric = env.numeric();
+        expect_items(&args[0])?.into_iter()
+            .reduce(|x, y| if numeric.compare(&y, &x) == Ordering::Less { y } else { x })
+            .ok_or_else(empty_sequence)
+    }
+}
+
+/// `max(s)`: the largest item of `s`, which must not be empty.
+#[derive(Debug, Clone)]
+pub struct MaxFunc;
+
+impl Func for MaxFunc {
+    fn call(&self, args: &[Value], env: &mut HeEnv) -> HeResult {
+        check_arity("max", Arity::Exact(1), args)?;
+
+        let numeric = env.numeric();
+        expect_items(&args[0])?.into_iter()
+            .reduce(|x, y| if numeric.compare(&x, &y) == Ordering::Less { y } else { x })
+            .ok_or_else(empty_sequence)
+    }
+}
+
+/// `sum(s)`: every element of `s`, or of the items of the list `s`, added up.
+#[derive(Debug, Clone)]
+pub struct SumFunc;
+
+impl Func for SumFunc {
+    fn call(&self, args: &[Value], env: &mut HeEnv) -> HeResult {
+        check_arity("sum", Arity::Exact(1), args)?;
+
+        let sum = expect_items(&args[0])?.iter()
+            .flat_map(|x| x.value.iter())
+            .fold(0u32, |sum, &x| sum.wrapping_add(x));
+        Ok(env.numeric().wrap(Value::new(vec![sum])))
+    }
+}
+
+/// `count(s, x)`: how many items of `s` are equal to `x`.
+#[derive(Debug, Clone)]
+pub struct CountFunc;
+
+impl Func for CountFunc {
+    fn call(&self, args: &[Value], env: &mut HeEnv) -> HeResult {
+        check_arity("count", Arity::Exact(2), args)?;
+
+        let numeric = env.numeric();
+        let count = expect_items(&args[0])?.iter().filter(|x| numeric.compare(x, &args[1]).is_eq()).count();
+        count_value(count, numeric)
+    }
+}
+
+/// `find(f, s)`: the index of the first item `x` of `s` for which `f(x)` is
+/// true, which is `len(s)` when there is none.
+#[derive(Debug, Clone)]
+pub struct FindFunc;
+
+impl Func for FindFunc {
+    fn call(&self, args: &[Value], env: &mut HeEnv) -> HeResult {
+        check_arity("find", Arity::Exact(2), args)?;
+
+        let func = expect_func(&args[0])?;
+        let items = expect_items(&args[1])?;
+        let mut index = items.len();
+        for (i, x) in items.into_iter().enumerate() {
+            if func.call(&[x], env)?.into() {
+                index = i;
+                break;
+            }
+        }
+        count_value(index, env.numeric())
+    }
+}
+
+/// `union(a, b)`: the items of `a` and then those of `b`, without repeats.
+/// It is a list when either is one.
+#[derive(Debug, Clone)]
+pub struct UnionFunc;
+
+impl Func for UnionFunc {
+    fn call(&self, args: &[Value], env: &mut HeEnv) -> HeResult {
+        check_arity("union", Arity::Exact(2), args)?;
+
+        let mut items = expect_items(&args[0])?;
+        items.extend(expect_items(&args[1])?);
+        let like = if args[1].as_list().is_some() { &args[1] } else { &args[0] };
+        Ok(collect_like(like, distinct(items, env.numeric())))
+    }
+}
+
+/// `intersect(a, b)`: the items of `a` that are also in `b`, without repeats.
+#[derive(Debug, Clone)]
+pub struct IntersectFunc;
+
+impl Func for IntersectFunc {
+    fn call(&self, args: &[Value], env: &mut HeEnv) -> HeResult {
+        check_arity("intersect", Arity::Exact(2), args)?;
+
+        let numeric = env.numeric();
+        let other = sorted(expect_items(&args[1])?, numeric);
+        let items = expect_items(&args[0])?.into_iter().filter(|x| has_item(&other, x, numeric)).collect();
+        Ok(collect_like(&args[0], distinct(items, numeric)))
+    }
+}
+
+/// `difference(a, b)`: the items of `a` that are not in `b`, without
+/// repeats.
+#[derive(Debug, Clone)]
+pub struct DifferenceFunc;
+
+impl Func for DifferenceFunc {
+    fn call(&self, args: &[Value], env: &mut HeEnv) -> HeResult {
+        check_arity("difference", Arity::Exact(2), args)?;
+
+        let numeric = env.numeric();
+        let other = sorted(expect_items(&args[1])?, numeric);
+        let items = expect_items(&args[0])?.into_iter().filter(|x| !has_item(&other, x, numeric)).collect();
+        Ok(collect_like(&args[0], distinct(items, numeric)))
+    }
+}
+
+/// The base of the `big*` builtins, given as their argument `at`: 10 unless
+/// given, and 0 stands for 2 to the power of the width, so that every
+/// element is a digit, as with base 256 for 8 bits.
+fn expect_big_base(args: &[Value], at: usize, numeric: Numeric) -> Result<u64, HeError> {
+    let full = 1u64 << numeric.bits();
+    let base = match args.get(at) {
+        Some(base) => expect_number(base.clone(), Span::default())?,
+        None => return Ok(10),
+    };
+    match base.value[..] {
+        [x] if numeric.number(x) == 0 => Ok(full),
+        [x] if numeric.number(x) >= 2 => Ok(numeric.number(x) as u64),
+        _ => Err(invalid_number(format!("base {} is not 0 or from 2 to {}", numeric.format(&base), full - 1))),
+    }
+}
+
+/// The little-endian digits of a `big*` argument.
+fn expect_digits(value: &Value, base: u64, numeric: Numeric) -> Result<Vec<u32>, HeError> {
+    let digits = numeric.wrap(expect_number(value.clone(), Span::default())?).value;
+    match digits.iter().find(|&&d| d as u64 >= base) {
+        Some(d) => Err(invalid_number(format!("digit {} is not below base {}", d, base))),
+        None => Ok(digits),
+    }
+}
+
+/// The two numbers and the base of `bigadd(a, b)` and the like.
+fn expect_big_args(name: &str, args: &[Value], numeric: Numeric) -> Result<(Vec<u32>, Vec<u32>, u64), HeError> {
+    check_arity(name, Arity::Between(2, 3), args)?;
+
+    let base = expect_big_base(args, 2, numeric)?;
+    Ok((expect_digits(&args[0], base, numeric)?, expect_digits(&args[1], base, numeric)?, base))
+}
+
+/// `bigadd(a, b)`: `a + b` for numbers of any size, given as little-endian
+/// decimal digits, or in the base passed last.
+#[derive(Debug, Clone)]
+pub struct BigAddFunc;
+
+impl Func for BigAddFunc {
+    fn call(&self, args: &[Value], env: &mut HeEnv) -> HeResult {
+        let (a, b, base) = expect_big_args("bigadd", args, env.numeric())?;
+        Ok(Value::new(big::add(&a, &b, base)))
+    }
+}
+
+/// `bigsub(a, b)`: `a - b`, where `b` is not larger than `a`.
+#[derive(Debug, Clone)]
+pub struct BigSubFunc;
+
+impl Func for BigSubFunc {
+    fn call(&self, args: &[Value], env: &mut HeEnv) -> HeResult {
+        let (a, b, base) = expect_big_args("bigsub", args, env.numeric())?;
+        big::sub(&a, &b, base)
+            .map(Value::new)
+            .ok_or(invalid_number("the difference is negative".to_string()))
+    }
+}
+
+/// `bigmul(a, b)`: `a * b`.
+#[derive(Debug, Clone)]
+pub struct BigMulFunc;
+
+impl Func for BigMulFunc {
+    fn call(&self, args: &[Value], env: &mut HeEnv) -> HeResult {
+        let (a, b, base) = expect_big_args("bigmul", args, env.numeric())?;
+        Ok(Value::new(big::mul(&a, &b, base)))
+    }
+}
+
+/// `bigdivmod(a, b)`: the list of `a / b` and `a % b`.
+#[derive(Debug, Clone)]
+pub struct BigDivModFunc;
+
+impl Func for BigDivModFunc {
+    fn call(&self, args: &[Value], env: &mut HeEnv) -> HeResult {
+        let (a, b, base) = expect_big_args("bigdivmod", args, env.numeric())?;
+        let (quotient, rest) = big::divmod(&a, &b, base)
+            .ok_or(HeError::DivisionByZero { span: Span::default() })?;
+        Ok(Value::list(vec![Value::new(quotient), Value::new(rest)]))
+    }
+}
+
+/// `bigcmp(a, b)`: 0, 1 or 2 as `a` is less than, equal to or greater than
+/// `b`, the same in every mode.
+#[derive(Debug, Clone)]
+pub struct BigCmpFunc;
+
+impl Func for BigCmpFunc {
+    fn call(&self, args: &[Value], env: &mut HeEnv) -> HeResult {
+        let numeric = env.numeric();
+        let (a, b, _) = expect_big_args("bigcmp", args, numeric)?;
+        Ok(Value::new(vec![(big::cmp(&a, &b) as i32 + 1) as u32]))
+    }
+}
+
+/// `bigpow(a, n)`: `a` to the power of the single element `n`.
+#[derive(Debug, Clone)]
+pub struct BigPowFunc;
+
+impl Func for BigPowFunc {
+    fn call(&self, args: &[Value], env: &mut HeEnv) -> HeResult {
+        check_arity("bigpow", Arity::Between(2, 3), args)?;
+
+        let numeric = env.numeric();
+        let base = expect_big_base(args, 2, numeric)?;
+        let a = expect_digits(&args[0], base, numeric)?;
+        let exponent = expect_number(args[1].clone(), Span::default())?;
+        match exponent.value[..] {
+            [n] if numeric.number(n) >= 0 => Ok(Value::new(big::pow(&a, numeric.number(n) as u64, base))),
+            _ => Err(invalid_number(format!("exponent {} is not a single element of at least 0", numeric.format(&exponent)))),
+        }
+    }
+}
+
+/// `map(f, s)`: `f(x)` for every element `x` of `s`, concatenated, or
+/// the list of them when `s` is a list.
+#[derive(Debug, Clone)]
+pub struct MapFunc;
+
+impl Func for MapFunc {
+    fn call(&self, args: &[Value], env: &mut HeEnv) -> HeResult {
+        check_arity("map", Arity::Exact(2), args)?;
+
+        let func = expect_func(&args[0])?;
+        let mut result = Vec::new();
+        for x in expect_items(&args[1])? {
+            result.push(func.call(&[x], env)?);
+        }
+        if args[1].as_list().is_some() {
+            return Ok(Value::list(result));
+        }
+        let mut value = Vec::new();
+        for y in result {
+            value.extend(expect_number(y, Span::default())?.value);
+        }
+        Ok(Value::new(value))
+    }
+}
+
+/// `filter(f, s)`: the elements or items `x` of `s` for which `f(x)` is
+/// true.
+#[derive(Debug, Clone)]
+pub struct FilterFunc;
+
+impl Func for FilterFunc {
+    fn call(&self, args: &[Value], env: &mut HeEnv) -> HeResult {
+        check_arity("filter", Arity::Exact(2), args)?;
+
+        let func = expect_func(&args[0])?;
+        let mut result = Vec::new();
+        for x in expect_items(&args[1])? {
+            if func.call(std::slice::from_ref(&x), env)?.into() {
+                result.push(x);
+            }
+        }
+        Ok(collect_like(&args[1], result))
+    }
+}
+
+/// `fold(f, init, s)`: `f(...f(f(init, s[0]), s[1])..., s[n - 1])`.
+#[derive(Debug, Clone)]
+pub struct FoldFunc;
+
+impl Func for FoldFunc {
+    fn call(&self, args: &[Value], env: &mut HeEnv) -> HeResult {
+        check_arity("fold", Arity::Exact(3), args)?;
+
+        let func = expect_func(&args[0])?;
+        let mut result = args[1].clone();
+        for x in expect_items(&args[2])? {
+            result = func.call(&[result, x], env)?;
+        }
+        Ok(result)
+    }
+}
+
+/// `get(m, key, default)`: the value of `key` in `m`, or `default`.
+#[derive(Debug, Clone)]
+pub struct GetFunc;
+
+impl Func for GetFunc {
+    fn call(&self, args: &[Value], _env: &mut HeEnv) -> HeResult {
+        check_arity("get", Arity::Exact(3), args)?;
+
+        let entries = expect_map(&args[0])?;
+        let key = expect_key(&args[1])?;
+        Ok(entries.get(&key).unwrap_or(&args[2]).clone())
+    }
+}
+
+/// `set(m, key, value)`: `m` with `key` set to `value`.
+#[derive(Debug, Clone)]
+pub struct SetFunc;
+
+impl Func for SetFunc {
+    fn call(&self, args: &[Value], _env: &mut HeEnv) -> HeResult {
+        check_arity("set", Arity::Exact(3), args)?;
+
+        let mut entries = expect_map(&args[0])?.clone();
+        entries.insert(expect_key(&args[1])?, args[2].clone());
+        Ok(Value::map(entries))
+    }
+}
+
+/// `contains(m, key)`: whether the map `m` has `key`, or whether `key` is an
+/// item of the list `m` or an element of the number `m`.
+#[derive(Debug, Clone)]
+pub struct ContainsFunc;
+
+impl Func for ContainsFunc {
+    fn call(&self, args: &[Value], env: &mut HeEnv) -> HeResult {
+        check_arity("contains", Arity::Exact(2), args)?;
+
+        let numeric = env.numeric();
+        match args[0].as_map() {
+            Some(entries) => Ok(entries.contains_key(&expect_key(&args[1])?).into()),
+            None => Ok(expect_items(&args[0])?.iter().any(|x| numeric.compare(x, &args[1]).is_eq()).into()),
+        }
+    }
+}
+
+/// `remove(m, key)`: `m` without `key`.
+#[derive(Debug, Clone)]
+pub struct RemoveFunc;
+
+impl Func for RemoveFunc {
+    fn call(&self, args: &[Value], _env: &mut HeEnv) -> HeResult {
+        check_arity("remove", Arity::Exact(2), args)?;
+
+        let mut entries = expect_map(&args[0])?.clone();
+        entries.remove(&expect_key(&args[1])?);
+        Ok(Value::map(entries))
+    }
+}
+
+/// `keys(m)`: the list of the keys of `m`, in order.
+#[derive(Debug, Clone)]
+pub struct KeysFunc;
+
+impl Func for KeysFunc {
+    fn call(&self, args: &[Value], _env: &mut HeEnv) -> HeResult {
+        check_arity("keys", Arity::Exact(1), args)?;
+
+        let entries = expect_map(&args[0])?;
+        Ok(Value::list(entries.keys().map(|key| Value::new(key.clone())).collect()))
     }
 }
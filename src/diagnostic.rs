@@ -0,0 +1,94 @@
+use std::fmt;
+
+pub use crate::error::*;
+
+/// Renders an error the way rustc does: message, location, the offending
+/// source line with a caret underline, and a hint when there is one.
+#[derive(Debug, Clone)]
+pub struct Diagnostic<'a> {
+    error: &'a HeError,
+    file_name: &'a str,
+    source: &'a str,
+}
+
+impl<'a> Diagnostic<'a> {
+    pub fn new(error: &'a HeError, file_name: &'a str, source: &'a str) -> Self {
+        Self { error, file_name, source }
+    }
+
+    fn hint(&self) -> Option<String> {
+        match self.error.root() {
+            HeError::UndefinedVariable { name, .. } => {
+                Some(format!("assign `{}` before using it, e.g. `{} = 0;`", name, name))
+            }
+            HeError::UndefinedFunction { name, .. } => {
+                Some(format!("define it first with `${}(...) {{ ... }};`", name))
+            }
+            HeError::FunctionRedefined { .. } => {
+                Some("functions cannot be redefined in the same scope".to_string())
+            }
+            HeError::ArityMismatch { expected, .. } => {
+                Some(format!("pass {} argument(s)", expected))
+            }
+            HeError::IndexOutOfRange { len: 0, .. } => {
+                Some("the sequence is empty".to_string())
+            }
+            HeError::IndexOutOfRange { len, .. } => {
+                Some(format!("valid indexs are 0 to {}", len - 1))
+            }
+            HeError::KeyNotFound { .. } => {
+                Some("check with `contains(m, key)`, or use `get(m, key, default)`".to_string())
+            }
+            HeError::DivisionByZero { .. } => {
+                Some("the right-hand side of `/` and `%` must not contain 0".to_string())
+            }
+            HeError::LengthMismatch { .. } => {
+                Some("use operands of the same length, or a single element".to_string())
+            }
+            HeError::LiteralOutOfRange { numeric, .. } | HeError::LengthOutOfRange { numeric, .. } => {
+                Some(format!("the largest {} is {}; `--bits` selects a wider width", numeric, numeric.max()))
+            }
+            HeError::InvalidRange { .. } => {
+                Some("bounds and step are single elements, and the step is not 0".to_string())
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<'a> fmt::Display for Diagnostic<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let span = self.error.span();
+        let (line, column) = span.line_col(self.source);
+        let line_text = self.source.lines().nth(line - 1).unwrap_or("");
+        let gutter = " ".repeat(line.to_string().len());
+
+        writeln!(f, "error: {}", self.error)?;
+        writeln!(f, "{}--> {}:{}:{}", gutter, self.file_name, line, column)?;
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", line, line_text)?;
+
+        // Keep tabs so the caret lines up with the source line.
+        let padding: String = line_text.chars()
+            .take(column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let rest = line_text.chars().count().saturating_sub(column - 1);
+        let width = self.source[span.start.min(self.source.len())..span.end.min(self.source.len())]
+            .chars()
+            .count()
+            .clamp(1, rest.max(1));
+        write!(f, "{} | {}{}", gutter, padding, "^".repeat(width))?;
+        if let Some(hint) = self.hint() {
+            write!(f, " {}", hint)?;
+        }
+        if let Some(trace) = self.error.trace_message(self.source) {
+            write!(f, "\n{} = note: {}", gutter, trace)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn render(error: &HeError, file_name: &str, source: &str) -> String {
+    Diagnostic::new(error, file_name, source).to_string()
+}
@@ -0,0 +1,251 @@
+use std::fmt;
+
+use crate::value::{Value, Numeric};
+
+/// Byte range `[start, end)` in the source a node or error comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    pub fn is_unknown(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// 1-based line and column of `start` in `source`.
+    pub fn line_col(&self, source: &str) -> (usize, usize) {
+        let start = self.start.min(source.len());
+        let before = &source[..start];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let column = before[line_start..].chars().count() + 1;
+        (line, column)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    Exact(usize),
+    AtLeast(usize),
+    /// From the first to the second, both included.
+    Between(usize, usize),
+}
+
+impl Arity {
+    pub fn accepts(&self, count: usize) -> bool {
+        match *self {
+            Arity::Exact(n) => count == n,
+            Arity::AtLeast(n) => count >= n,
+            Arity::Between(min, max) => (min..=max).contains(&count),
+        }
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Arity::Exact(n) => write!(f, "{}", n),
+            Arity::AtLeast(n) => write!(f, "at least {}", n),
+            Arity::Between(min, max) => write!(f, "{} to {}", min, max),
+        }
+    }
+}
+
+/// A call to a function: its name and where it was called from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub name: String,
+    pub span: Span,
+}
+
+impl Frame {
+    pub fn new(name: String, span: Span) -> Self {
+        Self { name, span }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HeError {
+    UndefinedVariable { name: String, span: Span },
+    UndefinedFunction { name: String, span: Span },
+    FunctionRedefined { name: String, span: Span },
+    ArityMismatch { name: String, expected: Arity, got: usize, span: Span },
+    IndexOutOfRange { index: usize, len: usize, span: Span },
+    KeyNotFound { key: Value, span: Span },
+    DivisionByZero { span: Span },
+    LengthMismatch { left: usize, right: usize, span: Span },
+    TypeMismatch { expected: &'static str, found: &'static str, span: Span },
+    LiteralOutOfRange { value: u32, numeric: Numeric, span: Span },
+    LengthOutOfRange { len: usize, numeric: Numeric, span: Span },
+    InvalidAssignment { name: String, reason: String, span: Span },
+    InvalidRange { reason: String, span: Span },
+    InvalidNumber { reason: String, span: Span },
+    InvalidArgument { reason: String, span: Span },
+    ImportFailed { path: String, reason: String, span: Span },
+    InvalidUtf8 { span: Span },
+    ParseError { message: String, span: Span },
+    IoError { message: String, span: Span },
+    /// Raised by `break` and `continue` and caught by the enclosing loop; it
+    /// only surfaces as an error when there is no such loop.
+    Break { span: Span },
+    Continue { span: Span },
+    /// Raised by `return` and caught by the function being called.
+    Return { value: Value, span: Span },
+    /// An error raised inside a `$` function, with the calls that led to it,
+    /// outermost first.
+    Traced { error: Box<HeError>, trace: Vec<Frame> },
+}
+
+impl HeError {
+    /// The error itself, without the call stack it may be wrapped in.
+    pub fn root(&self) -> &HeError {
+        match self {
+            HeError::Traced { error, .. } => error.root(),
+            _ => self,
+        }
+    }
+
+    pub fn trace(&self) -> &[Frame] {
+        match self {
+            HeError::Traced { trace, .. } => trace,
+            _ => &[],
+        }
+    }
+
+    /// Attaches the call stack, unless an inner call already did.
+    pub fn with_trace(self, trace: &[Frame]) -> Self {
+        match self {
+            HeError::Traced { .. } => self,
+            _ => HeError::Traced { error: Box::new(self), trace: trace.to_vec() },
+        }
+    }
+
+    /// Renders the call stack as `in f (line 3) <- called from main (line 9)`.
+    pub fn trace_message(&self, source: &str) -> Option<String> {
+        let trace = self.trace();
+        let last = trace.last()?;
+        let mut message = format!("in {} (line {})", last.name, self.span().line_col(source).0);
+        for (i, frame) in trace.iter().enumerate().rev() {
+            let caller = if i == 0 { "main" } else { trace[i - 1].name.as_str() };
+            message += &format!(" <- called from {} (line {})", caller, frame.span.line_col(source).0);
+        }
+        Some(message)
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            HeError::Traced { error, .. } => error.span(),
+            HeError::UndefinedVariable { span, .. }
+            | HeError::UndefinedFunction { span, .. }
+            | HeError::FunctionRedefined { span, .. }
+            | HeError::ArityMismatch { span, .. }
+            | HeError::IndexOutOfRange { span, .. }
+            | HeError::KeyNotFound { span, .. }
+            | HeError::DivisionByZero { span }
+            | HeError::LengthMismatch { span, .. }
+            | HeError::TypeMismatch { span, .. }
+            | HeError::LiteralOutOfRange { span, .. }
+            | HeError::LengthOutOfRange { span, .. }
+            | HeError::InvalidAssignment { span, .. }
+            | HeError::InvalidRange { span, .. }
+            | HeError::InvalidNumber { span, .. }
+            | HeError::InvalidArgument { span, .. }
+            | HeError::ImportFailed { span, .. }
+            | HeError::InvalidUtf8 { span }
+            | HeError::ParseError { span, .. }
+            | HeError::IoError { span, .. }
+            | HeError::Break { span }
+            | HeError::Continue { span }
+            | HeError::Return { span, .. } => *span,
+        }
+    }
+
+    fn span_mut(&mut self) -> &mut Span {
+        match self {
+            HeError::Traced { error, .. } => error.span_mut(),
+            HeError::UndefinedVariable { span, .. }
+            | HeError::UndefinedFunction { span, .. }
+            | HeError::FunctionRedefined { span, .. }
+            | HeError::ArityMismatch { span, .. }
+            | HeError::IndexOutOfRange { span, .. }
+            | HeError::KeyNotFound { span, .. }
+            | HeError::DivisionByZero { span }
+            | HeError::LengthMismatch { span, .. }
+            | HeError::TypeMismatch { span, .. }
+            | HeError::LiteralOutOfRange { span, .. }
+            | HeError::LengthOutOfRange { span, .. }
+            | HeError::InvalidAssignment { span, .. }
+            | HeError::InvalidRange { span, .. }
+            | HeError::InvalidNumber { span, .. }
+            | HeError::InvalidArgument { span, .. }
+            | HeError::ImportFailed { span, .. }
+            | HeError::InvalidUtf8 { span }
+            | HeError::ParseError { span, .. }
+            | HeError::IoError { span, .. }
+            | HeError::Break { span }
+            | HeError::Continue { span }
+            | HeError::Return { span, .. } => span,
+        }
+    }
+
+    /// Builtins raise errors without knowing where they were called from;
+    /// the caller fills in its own span if none was set.
+    pub fn or_span(mut self, span: Span) -> Self {
+        if self.span().is_unknown() {
+            *self.span_mut() = span;
+        }
+        self
+    }
+}
+
+impl fmt::Display for HeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HeError::UndefinedVariable { name, .. } => write!(f, "Variable {} not found", name),
+            HeError::UndefinedFunction { name, .. } => write!(f, "Function {} not found", name),
+            HeError::FunctionRedefined { name, .. } => write!(f, "Function {} already defined", name),
+            HeError::ArityMismatch { name, expected, got, .. } => {
+                write!(f, "{} requires {} argument(got {})", name, expected, got)
+            }
+            HeError::IndexOutOfRange { index, len, .. } => {
+                write!(f, "Index {} out of range(length {})", index, len)
+            }
+            HeError::KeyNotFound { key, .. } => write!(f, "Key {} not found", key),
+            HeError::DivisionByZero { .. } => write!(f, "Division by zero"),
+            HeError::LengthMismatch { left, right, .. } => {
+                write!(f, "Cannot combine values of length {} and {}", left, right)
+            }
+            HeError::TypeMismatch { expected, found, .. } => {
+                write!(f, "Expected a {}, found a {}", expected, found)
+            }
+            HeError::LiteralOutOfRange { value, numeric, .. } => {
+                write!(f, "Literal {} does not fit in {}", value, numeric)
+            }
+            HeError::LengthOutOfRange { len, numeric, .. } => {
+                write!(f, "Length {} does not fit in {}", len, numeric)
+            }
+            HeError::InvalidAssignment { name, reason, .. } => {
+                write!(f, "Cannot assign to variable {}: {}", name, reason)
+            }
+            HeError::InvalidRange { reason, .. } => write!(f, "Invalid range: {}", reason),
+            HeError::InvalidNumber { reason, .. } => write!(f, "Invalid number: {}", reason),
+            HeError::InvalidArgument { reason, .. } => write!(f, "Invalid argument: {}", reason),
+            HeError::ImportFailed { path, reason, .. } => write!(f, "Cannot import {}: {}", path, reason),
+            HeError::InvalidUtf8 { .. } => write!(f, "Value is not valid UTF-8"),
+            HeError::ParseError { message, .. } => write!(f, "{}", message),
+            HeError::IoError { message, .. } => write!(f, "IO Err: {}", message),
+            HeError::Break { .. } => write!(f, "`break` outside of a loop"),
+            HeError::Continue { .. } => write!(f, "`continue` outside of a loop"),
+            HeError::Return { .. } => write!(f, "`return` outside of a function"),
+            HeError::Traced { error, .. } => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for HeError {}
@@ -1,6 +1,9 @@
 mod value;
 pub use value::*;
 
+mod error;
+pub use error::*;
+
 mod ast;
 pub use ast::*;
 
@@ -11,35 +14,49 @@ mod def;
 pub use def::*;
 
 mod cpp_code;
-pub use cpp_code::*;
+
+mod big;
+
+mod diagnostic;
+pub use diagnostic::*;
 
 pub fn eval(input: &str, env: &mut HeEnv) -> HeResult {
-    let ast = block_ast(input);
-    if ast.is_err() {
-        return Err(format!("Parser Err: {}", ast.unwrap_err()));
-    }
-    let ast = ast.unwrap();
-    if !ast.0.trim().is_empty() {
-        return Err(format!("Cannot parse \"{}\"", ast.0.trim()));
-    }
-    // println!("input\"{}\"", ast.0);
-    let ast = ast.1;
+    let ast = parse(input)?;
+    enter_prelude(env, false, &mut CppCode::default())?;
     ast.eval(env)
 }
 
-pub fn gen_code(input: &str, env: &mut HeEnv) -> Result<String, String> {
-    let ast = block_ast(input);
-    if ast.is_err() {
-        return Err(format!("Parser Err: {}", ast.unwrap_err()));
-    }
-    let ast = ast.unwrap();
-    if !ast.0.trim().is_empty() {
-        return Err(format!("Cannot parse \"{}\"", ast.0.trim()));
-    }
-    // println!("input\"{}\"", ast.0);
-    let ast = ast.1;
+/// Every syntax error in `input`, without running it.
+pub fn check(input: &str) -> Vec<HeError> {
+    parse_all(input).errors
+}
+
+pub fn gen_code(input: &str, env: &mut HeEnv) -> Result<String, HeError> {
+    let block = parse_block(input)?;
+    // Top-level definitions live as long as the program, so functions can
+    // refer to them after any block they were made in has ended.
+    let mut program = CppCode { static_depth: Some(0), ..CppCode::default() };
+    enter_prelude(env, true, &mut program)?;
+    program.enter_layer();
+    block.gen_statements(env, &mut program)?;
+    program.leave_layer();
+
     let mut code = CppCode::default();
-    ast.gen_code(env, &mut code)?;
+    // `cpp_header.cpp` has to be built for the numbers the code assumes.
+    let numeric = env.numeric();
+    let flags = format!("-DHERANG_BITS={} -DHERANG_SIGNED={}", numeric.bits(), numeric.is_signed() as u8);
+    code.push_line(format!(
+        "static_assert(HERANG_BITS == {} && HERANG_SIGNED == {}, \"compile with {}\");",
+        numeric.bits(),
+        numeric.is_signed() as u8,
+        flags,
+    ).as_str());
+    // Imported files go first, each once, so their functions exist before
+    // any import refers to them.
+    for module in env.module_code() {
+        code.push(module);
+    }
+    code.push(&program.code);
     Ok(code.code)
 }
 
@@ -49,7 +66,36 @@ pub fn init_env(env: &mut HeEnv) -> HeResult {
     env.set_func("sprint".to_string(), Box::new(SPrintFunc))?;
     env.set_func("cyber".to_string(), Box::new(CyberFunc))?;
     env.set_func("trim".to_string(), Box::new(TrimFunc))?;
+    env.set_func("parse_int".to_string(), Box::new(ParseIntFunc))?;
+    env.set_func("to_str".to_string(), Box::new(ToStrFunc))?;
+    env.set_func("fmt".to_string(), Box::new(FmtFunc))?;
     env.set_func("len".to_string(), Box::new(LenFunc))?;
     env.set_func("slice".to_string(), Box::new(SliceFunc))?;
+    env.set_func("sort".to_string(), Box::new(SortFunc))?;
+    env.set_func("sort_desc".to_string(), Box::new(SortDescFunc))?;
+    env.set_func("unique".to_string(), Box::new(UniqueFunc))?;
+    env.set_func("binary_search".to_string(), Box::new(BinarySearchFunc))?;
+    env.set_func("min".to_string(), Box::new(MinFunc))?;
+    env.set_func("max".to_string(), Box::new(MaxFunc))?;
+    env.set_func("sum".to_string(), Box::new(SumFunc))?;
+    env.set_func("count".to_string(), Box::new(CountFunc))?;
+    env.set_func("find".to_string(), Box::new(FindFunc))?;
+    env.set_func("union".to_string(), Box::new(UnionFunc))?;
+    env.set_func("intersect".to_string(), Box::new(IntersectFunc))?;
+    env.set_func("difference".to_string(), Box::new(DifferenceFunc))?;
+    env.set_func("bigadd".to_string(), Box::new(BigAddFunc))?;
+    env.set_func("bigsub".to_string(), Box::new(BigSubFunc))?;
+    env.set_func("bigmul".to_string(), Box::new(BigMulFunc))?;
+    env.set_func("bigdivmod".to_string(), Box::new(BigDivModFunc))?;
+    env.set_func("bigcmp".to_string(), Box::new(BigCmpFunc))?;
+    env.set_func("bigpow".to_string(), Box::new(BigPowFunc))?;
+    env.set_func("map".to_string(), Box::new(MapFunc))?;
+    env.set_func("filter".to_string(), Box::new(FilterFunc))?;
+    env.set_func("fold".to_string(), Box::new(FoldFunc))?;
+    env.set_func("get".to_string(), Box::new(GetFunc))?;
+    env.set_func("set".to_string(), Box::new(SetFunc))?;
+    env.set_func("contains".to_string(), Box::new(ContainsFunc))?;
+    env.set_func("remove".to_string(), Box::new(RemoveFunc))?;
+    env.set_func("keys".to_string(), Box::new(KeysFunc))?;
     Ok(Value::default())
 }
@@ -1,4 +1,3 @@
-use herang;
 use std::{io::Write, error::Error};
 
 extern crate clap;
@@ -13,6 +12,34 @@ struct Args {
 
    #[arg(short, long)]
    cpp: bool,
+
+   /// Only report syntax errors
+   #[arg(long)]
+   check: bool,
+
+   /// Width of every value element: 8, 16 or 32
+   #[arg(long, default_value_t = 8, value_parser = parse_bits)]
+   bits: u32,
+
+   /// Treat value elements as signed two's complement numbers
+   #[arg(long)]
+   signed: bool,
+
+   /// Let functions see their caller's variables instead of the ones where
+   /// they were defined
+   #[arg(long)]
+   dynamic_scope: bool,
+
+   /// Start without the functions of the prelude, such as `reverse`
+   #[arg(long)]
+   no_prelude: bool,
+}
+
+fn parse_bits(s: &str) -> Result<u32, String> {
+    s.parse().ok()
+        .and_then(herang::Numeric::new)
+        .map(|numeric| numeric.bits())
+        .ok_or_else(|| format!("expected one of {:?}", herang::Numeric::WIDTHS))
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -20,27 +47,42 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     let mut env = herang::HeEnv::new();
     herang::init_env(&mut env)?;
+    env.set_numeric(herang::Numeric::new(args.bits).unwrap().with_signed(args.signed));
+    env.set_dynamic_scope(args.dynamic_scope);
+    env.set_prelude(!args.no_prelude);
     if let Some(path) = args.path.as_deref() {
         let source = std::fs::read_to_string(path)?;
-        if args.cpp {
-            let result = herang::gen_code(&source, &mut env);
-            println!("{}", match result {
-                Ok(code) => code,
-                Err(msg) => msg,
-            });
+        env.set_file(std::path::PathBuf::from(path));
+        let errors = herang::check(&source);
+        for err in errors.iter() {
+            eprintln!("{}\n", herang::render(err, path, &source));
+        }
+        if !errors.is_empty() {
+            std::process::exit(1);
+        }
+        if args.check {
+            return Ok(());
+        }
+        let result = if args.cpp {
+            herang::gen_code(&source, &mut env).map(|code| println!("{}", code))
         } else {
-            herang::eval(&source, &mut env)?;
+            herang::eval(&source, &mut env).map(|_| ())
+        };
+        if let Err(err) = result {
+            eprintln!("{}", herang::render(&err, path, &source));
+            std::process::exit(1);
         }
     } else {
         loop {
             print!("herang> ");
             std::io::stdout().flush().expect("Unable to flush stdout");
             let mut input = String::new();
-            let _ = std::io::stdin().read_line(&mut input);
-    
-            let result = herang::eval(&input, &mut env);
-            if result.is_err() {
-                println!("Err: {}", result.unwrap_err());
+            if std::io::stdin().read_line(&mut input)? == 0 {
+                break;
+            }
+
+            if let Err(err) = herang::eval(&input, &mut env) {
+                println!("{}", herang::render(&err, "<repl>", &input));
             }
         }
     }
@@ -3,271 +3,818 @@ extern crate nom;
 use nom::{
     IResult,
     bytes::complete::{tag, take_while1},
-    character::complete::{u32, multispace0, multispace1},
-    sequence::{tuple, pair, preceded},
+    character::complete::{u32, multispace0},
+    sequence::{tuple, pair, preceded, terminated},
     branch::alt,
-    multi::{separated_list0, separated_list1, many0},
-    combinator::map, AsChar,
+    multi::{many1, separated_list0, separated_list1},
+    combinator::{map, cut, not}, AsChar, Slice,
+    error::{context, ContextError, ErrorKind, ParseError},
 };
+use nom_locate::LocatedSpan;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
 
-pub use crate::value::*;
 pub use crate::ast::*;
 
-pub fn value(input: &str) -> IResult<&str, Value> {
+pub type Input<'a> = LocatedSpan<&'a str, &'a ParseState>;
+
+/// A `// line` or `/* block */` comment, kept so tools such as a formatter
+/// can put it back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Comment {
+    pub text: String,
+    pub span: Span,
+}
+
+/// Shared by every `Input` of one parse; collects the syntax errors the
+/// parser recovered from and the comments it skipped.
+#[derive(Debug, Default)]
+pub struct ParseState {
+    errors: RefCell<Vec<HeError>>,
+    comments: RefCell<BTreeMap<usize, Comment>>,
+}
+
+impl ParseState {
+    pub fn report(&self, error: HeError) {
+        self.errors.borrow_mut().push(error);
+    }
+
+    /// Backtracking skips the same comment more than once, so comments are
+    /// keyed by where they start.
+    pub fn comment(&self, comment: Comment) {
+        self.comments.borrow_mut().insert(comment.span.start, comment);
+    }
+}
+
+/// Everything `parse_all` found in a source file.
+#[derive(Debug)]
+pub struct ParseOutput {
+    pub ast: Box<dyn AST>,
+    pub errors: Vec<HeError>,
+    pub comments: Vec<Comment>,
+}
+
+pub type PResult<'a, O> = IResult<Input<'a>, O, SyntaxError<'a>>;
+
+/// Parse error that remembers the furthest position reached and, if known,
+/// what the parser expected to find there.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxError<'a> {
+    pub input: Input<'a>,
+    pub expected: Option<&'static str>,
+}
+
+impl<'a> ParseError<Input<'a>> for SyntaxError<'a> {
+    fn from_error_kind(input: Input<'a>, _kind: ErrorKind) -> Self {
+        Self { input, expected: None }
+    }
+
+    fn append(_input: Input<'a>, _kind: ErrorKind, other: Self) -> Self {
+        other
+    }
+
+    fn or(self, other: Self) -> Self {
+        if other.input.location_offset() >= self.input.location_offset() {
+            other
+        } else {
+            self
+        }
+    }
+}
+
+impl<'a> ContextError<Input<'a>> for SyntaxError<'a> {
+    fn add_context(_input: Input<'a>, ctx: &'static str, mut other: Self) -> Self {
+        other.expected.get_or_insert(ctx);
+        other
+    }
+}
+
+impl<'a> From<SyntaxError<'a>> for HeError {
+    fn from(e: SyntaxError<'a>) -> Self {
+        let start = e.input.location_offset();
+        let found = e.input.fragment().chars().next();
+        let span = Span::new(start, start + found.map_or(0, char::len_utf8));
+        let found = match found {
+            Some(c) => format!("`{}`", c),
+            None => "end of input".to_string(),
+        };
+        let message = match e.expected {
+            Some(expected) => format!("expected {}, found {}", expected, found),
+            None => format!("unexpected {}", found),
+        };
+        HeError::ParseError { message, span }
+    }
+}
+
+fn span(start: &Input, end: &Input) -> Span {
+    Span::new(start.location_offset(), end.location_offset())
+}
+
+/// Length of the comment at the start of `text`, or `None` if there is none.
+/// An unterminated block comment runs to the end of `text`.
+fn comment_len(text: &str) -> Option<usize> {
+    if text.starts_with("//") {
+        return Some(text.find('\n').unwrap_or(text.len()));
+    }
+    text.strip_prefix("/*")
+        .map(|rest| rest.find("*/").map_or(text.len(), |i| i + 4))
+}
+
+/// Whitespace and comments, which may appear anywhere whitespace may.
+pub fn ws(input: Input) -> PResult<Input> {
+    let start = input;
+    let (mut input, _) = multispace0(input)?;
+    while let Some(len) = comment_len(input.fragment()) {
+        let text = &input.fragment()[..len];
+        if text.starts_with("/*") && (len < 4 || !text.ends_with("*/")) {
+            return Err(nom::Err::Failure(SyntaxError {
+                input: input.slice(len..),
+                expected: Some("`*/` to close the block comment"),
+            }));
+        }
+        let comment_end = input.slice(len..);
+        input.extra.comment(Comment { text: text.to_string(), span: span(&input, &comment_end) });
+        (input, _) = multispace0(comment_end)?;
+    }
+    let consumed = input.location_offset() - start.location_offset();
+    Ok((input, start.slice(..consumed)))
+}
+
+fn ws1(input: Input) -> PResult<Input> {
+    let (rest, consumed) = ws(input)?;
+    if consumed.fragment().is_empty() {
+        return Err(nom::Err::Error(SyntaxError::from_error_kind(input, ErrorKind::MultiSpace)));
+    }
+    Ok((rest, consumed))
+}
+
+/// Once a construct has been recognized, a missing token is a hard error
+/// instead of a reason to try the next alternative.
+fn expect<'a>(token: &'static str, what: &'static str) -> impl FnMut(Input<'a>) -> PResult<'a, Input<'a>> {
+    preceded(ws, cut(context(what, tag(token))))
+}
+
+/// The right-hand side of an operator that has already been consumed.
+fn operand<'a>(parser: impl FnMut(Input<'a>) -> PResult<'a, Box<dyn AST>>) -> impl FnMut(Input<'a>) -> PResult<'a, Box<dyn AST>> {
+    cut(context("an expression after the operator", parser))
+}
+
+/// `=` that does not start `==`.
+fn assign_sign(input: Input) -> PResult<Input> {
+    preceded(ws, terminated(tag("="), not(tag("="))))(input)
+}
+
+/// Parses the whole source, recovering from syntax errors, and returns
+/// whatever could be parsed together with every error found.
+pub fn parse_all(source: &str) -> ParseOutput {
+    let (block, errors, comments) = parse_statements(source);
+    ParseOutput { ast: Box::new(block), errors, comments }
+}
+
+fn parse_statements(source: &str) -> (BlockAST, Vec<HeError>, Vec<Comment>) {
+    let state = ParseState::default();
+    let mut input = Input::new_extra(source, &state);
+    let mut statements = Vec::new();
+    loop {
+        let (rest, block) = match statement_list(input) {
+            Ok(result) => result,
+            Err(_) => unreachable!("statement_list recovers from every error"),
+        };
+        statements.extend(block);
+        // `statement_list` only stops at the end of input or at a `}` with
+        // no matching `{`.
+        if rest.fragment().is_empty() {
+            break;
+        }
+        state.report(SyntaxError { input: rest, expected: None }.into());
+        // The stray `}` most likely ends a statement, so skip its `;` too.
+        let rest = rest.slice(1..);
+        input = match pair(ws, tag::<_, _, SyntaxError>(";"))(rest) {
+            Ok((rest, _)) => rest,
+            Err(_) => rest,
+        };
+    }
+    (
+        BlockAST::new(statements),
+        state.errors.into_inner(),
+        state.comments.into_inner().into_values().collect(),
+    )
+}
+
+pub fn parse(source: &str) -> Result<Box<dyn AST>, HeError> {
+    parse_block(source).map(|block| Box::new(block) as Box<dyn AST>)
+}
+
+/// Like `parse`, keeping the top-level statements together, as imports
+/// need them.
+pub fn parse_block(source: &str) -> Result<BlockAST, HeError> {
+    let (block, errors, _) = parse_statements(source);
+    match errors.into_iter().next() {
+        Some(error) => Err(error),
+        None => Ok(block),
+    }
+}
+
+/// Skips past the next `;` or up to the next `}` that closes the current
+/// block, so parsing can resume after a broken statement.
+fn skip_statement(input: Input) -> Input {
+    let text = input.fragment();
+    let mut depth = 0usize;
+    let mut i = 0;
+    while let Some(c) = text[i..].chars().next() {
+        if let Some(len) = comment_len(&text[i..]).or_else(|| quoted_len(&text[i..])) {
+            i += len;
+            continue;
+        }
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => return input.slice(i..),
+            '}' => depth -= 1,
+            ';' if depth == 0 => return input.slice(i + 1..),
+            _ => {}
+        }
+        i += c.len_utf8();
+    }
+    input.slice(text.len()..)
+}
+
+pub fn value(input: Input) -> PResult<Value> {
     let (input, value) = u32(input)?;
     Ok((input, Value::new(vec![value])))
 }
 
+/// Length of the `"string"` or `'c'` literal at the start of `text`,
+/// ignoring escaped quotes. An unterminated literal runs to the end of `text`.
+fn quoted_len(text: &str) -> Option<usize> {
+    let quote = text.chars().next().filter(|&c| c == '"' || c == '\'')?;
+    let mut escaped = false;
+    for (i, c) in text.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            _ if c == quote => return Some(i + 1),
+            _ => {}
+        }
+    }
+    Some(text.len())
+}
+
+/// One character of a literal, with `\n`, `\t`, `\r`, `\0`, `\\`, `\"`,
+/// `\'` and `\xNN` escapes, appended to `bytes` as UTF-8.
+fn literal_char<'a>(input: Input<'a>, bytes: &mut Vec<u8>) -> PResult<'a, ()> {
+    let invalid = |input| nom::Err::Failure(SyntaxError {
+        input,
+        expected: Some("an escape such as `\\n`, `\\\"` or `\\x41`"),
+    });
+    let mut chars = input.fragment().chars();
+    match chars.next() {
+        Some('\\') => {
+            let escape = input.slice(1..);
+            let byte = match chars.next() {
+                Some('n') => b'\n',
+                Some('t') => b'\t',
+                Some('r') => b'\r',
+                Some('0') => 0,
+                Some('\\') => b'\\',
+                Some('"') => b'"',
+                Some('\'') => b'\'',
+                Some('x') => {
+                    let digits = escape.fragment().get(1..3).unwrap_or("");
+                    let byte = u8::from_str_radix(digits, 16)
+                        .ok()
+                        .filter(|_| digits.chars().all(|c| c.is_ascii_hexdigit()))
+                        .ok_or_else(|| invalid(escape))?;
+                    bytes.push(byte);
+                    return Ok((input.slice(4..), ()));
+                }
+                _ => return Err(invalid(escape)),
+            };
+            bytes.push(byte);
+            Ok((input.slice(2..), ()))
+        }
+        Some(c) => {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            Ok((input.slice(c.len_utf8()..), ()))
+        }
+        None => Err(nom::Err::Error(SyntaxError::from_error_kind(input, ErrorKind::Char))),
+    }
+}
+
+/// `"text"`, one element per UTF-8 byte.
+pub fn string_value(input: Input) -> PResult<Value> {
+    let (mut input, _) = tag("\"")(input)?;
+    let mut bytes = Vec::new();
+    while !input.fragment().starts_with('"') {
+        if input.fragment().is_empty() {
+            return Err(nom::Err::Failure(SyntaxError {
+                input,
+                expected: Some("`\"` to close the string"),
+            }));
+        }
+        (input, _) = literal_char(input, &mut bytes)?;
+    }
+    let (input, _) = tag("\"")(input)?;
+    Ok((input, Value::new(bytes.into_iter().map(u32::from).collect())))
+}
+
+/// `'c'`, the UTF-8 bytes of a single character.
+pub fn char_value(input: Input) -> PResult<Value> {
+    let (input, _) = tag("'")(input)?;
+    if input.fragment().starts_with('\'') {
+        return Err(nom::Err::Failure(SyntaxError { input, expected: Some("a character") }));
+    }
+    let mut bytes = Vec::new();
+    let (input, _) = literal_char(input, &mut bytes).map_err(|e| match e {
+        nom::Err::Error(_) => nom::Err::Failure(SyntaxError { input, expected: Some("a character") }),
+        e => e,
+    })?;
+    let (input, _) = cut(context("`'` to close the character", tag("'")))(input)?;
+    Ok((input, Value::new(bytes.into_iter().map(u32::from).collect())))
+}
+
 pub fn is_alpha_or_underscore(c: char) -> bool {
     c.is_alpha() || c == '_'
 }
 
-pub fn identifier(input: &str) -> IResult<&str, String> {
-    map(take_while1(is_alpha_or_underscore), |s: &str| s.to_string())(input)
+pub fn identifier(input: Input) -> PResult<String> {
+    map(take_while1(is_alpha_or_underscore), |s: Input| s.fragment().to_string())(input)
+}
+
+/// `name` or `module::name`, for functions brought in by `import`.
+fn qualified_name(input: Input) -> PResult<String> {
+    let (input, name) = identifier(input)?;
+    match preceded(tag::<_, _, SyntaxError>("::"), identifier)(input) {
+        Ok((input, member)) => Ok((input, format!("{}::{}", name, member))),
+        Err(_) => Ok((input, name)),
+    }
 }
 
 // ast
 
-fn value_ast(input: &str) -> IResult<&str, Box<dyn AST>> {
-    map(value, |v| Box::new(ValueAST::new(v)) as Box<dyn AST>)(input)
+fn value_ast(input: Input) -> PResult<Box<dyn AST>> {
+    let start = input;
+    if let Ok((input, value)) = value(input) {
+        return Ok((input, Box::new(ValueAST::new(value, span(&start, &input)))));
+    }
+    let (input, value) = alt((string_value, char_value))(input)?;
+    Ok((input, Box::new(ValueAST::text(value, span(&start, &input)))))
 }
 
-fn var_ref_ast(input: &str) -> IResult<&str, Box<dyn AST>> {
-    let (input, var_name) = identifier(input)?;
-    let (input, _) = pair(multispace0, tag("["))(input)?;
+/// `[expr]` after a value or a variable.
+fn index(input: Input) -> PResult<Box<dyn AST>> {
+    let (input, _) = pair(ws, tag("["))(input)?;
     let (input, index) = expr_ast(input)?;
-    let (input, _) = pair(multispace0, tag("]"))(input)?;
+    let (input, _) = expect("]", "`]` to close the index")(input)?;
+    Ok((input, index))
+}
 
-    Ok((input, Box::new(VarRefAST::new(var_name, index))))
+fn list_ast(input: Input) -> PResult<Box<dyn AST>> {
+    let (input, _) = tag("[")(input)?;
+    let (input, items) = separated_list0(
+        tuple((ws, tag(","), ws)),
+        expr_ast,
+    )(input)?;
+    let (input, _) = ws(input)?;
+    let (input, _) = expect("]", "`]` to close the list")(input)?;
+    Ok((input, Box::new(ListAST::new(items))))
 }
 
-fn var_ast(input: &str) -> IResult<&str, Box<dyn AST>> {
-    map(identifier, |v| Box::new(VarAST::new(v)) as Box<dyn AST>)(input)
+fn var_ast(input: Input) -> PResult<Box<dyn AST>> {
+    let start = input;
+    let (input, var_name) = qualified_name(input)?;
+    Ok((input, Box::new(VarAST::new(var_name, span(&start, &input)))))
 }
 
-fn func_call_ast(input: &str) -> IResult<&str, Box<dyn AST>> {
-    let (input, func_name) = identifier(input)?;
+fn func_call_ast(input: Input) -> PResult<Box<dyn AST>> {
+    let start = input;
+    let (input, func_name) = qualified_name(input)?;
 
-    let (input, _) = pair(multispace0, tag("("))(input)?;
+    let (input, _) = pair(ws, tag("("))(input)?;
     let (input, args) = separated_list0(
-        tuple((multispace0, tag(","), multispace0)),
+        tuple((ws, tag(","), ws)),
         expr_ast,
     )(input)?;
-    let (input, _) = pair(multispace0, tag(")"))(input)?;
+    let (input, _) = expect(")", "`)` to close the argument list")(input)?;
+
+    Ok((input, Box::new(FuncCallAST::new(func_name, args, span(&start, &input))) as Box<dyn AST>))
+}
 
-    Ok((input, Box::new(FuncCallAST::new(func_name, args)) as Box<dyn AST>))
+fn map_entry(input: Input) -> PResult<(Box<dyn AST>, Box<dyn AST>)> {
+    let (input, key) = expr_ast(input)?;
+    let (input, _) = ws(input)?;
+    let (input, _) = expect(":", "`:` after the key")(input)?;
+    let (input, value) = operand(expr_ast)(input)?;
+    Ok((input, (key, value)))
 }
 
-pub fn value_expr_ast(input: &str) -> IResult<&str, Box<dyn AST>> {
-    let (input, _) = multispace0(input)?;
+fn map_ast(input: Input) -> PResult<Box<dyn AST>> {
+    let start = input;
+    let (input, _) = tag("{")(input)?;
+    let (input, entries) = separated_list0(
+        tuple((ws, tag(","), ws)),
+        map_entry,
+    )(input)?;
+    let (input, _) = ws(input)?;
+    let (input, _) = expect("}", "`}` to close the map")(input)?;
+    Ok((input, Box::new(MapAST::new(entries, span(&start, &input)))))
+}
 
-    if input.starts_with("(") {
+/// A value, possibly indexed as in `m[i][j]`.
+pub fn value_expr_ast(input: Input) -> PResult<Box<dyn AST>> {
+    let (input, _) = ws(input)?;
+
+    let start = input;
+    let (mut input, mut expr) = if input.fragment().starts_with('(') {
         let (input, _) = tag("(")(input)?;
         let (input, expr) = expr_ast(input)?;
-        let (input, _) = pair(multispace0, tag(")"))(input)?;
-        Ok((input, expr))
+        let (input, _) = expect(")", "`)` to close the parenthesis")(input)?;
+        (input, expr)
     } else {
         let (input, ast) = alt((
+            lambda_ast,
             func_call_ast,
             value_ast,
-            var_ref_ast,
+            list_ast,
+            map_ast,
             var_ast,
         ))(input)?;
-        Ok((input, Box::new(ExprAST::new(ast))))
+        (input, Box::new(ExprAST::new(ast)) as Box<dyn AST>)
+    };
+    loop {
+        match index(input) {
+            Ok((rest, indexs)) => {
+                input = rest;
+                expr = Box::new(IndexAST::new(expr, indexs, span(&start, &input)));
+            }
+            Err(nom::Err::Error(_)) => return Ok((input, expr)),
+            Err(e) => return Err(e),
+        }
     }
 }
 
-fn or_expr_ast(input: &str) -> IResult<&str, Box<dyn AST>> {
-    let (input, _) = multispace0(input)?;
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinaryOp {
+    Concat,
+    Arithmetic(ArithmeticExprType),
+    Equality(EqualityExprType),
+    Logical(LogicalExprType),
+    /// `..`, or `..=` when inclusive; see `binary_expr_ast` for its step.
+    Range(bool),
+}
 
-    let (input, mut expr) = separated_list1(
-        tuple((multispace0, tag("|"), multispace0)),
-        value_expr_ast,
-    )(input)?;
-    expr.reverse();
-    let mut ast = expr.pop().unwrap();
-    while let Some(right) = expr.pop() {
-        ast = Box::new(OrExprAST::new(ast, right));
+impl BinaryOp {
+    fn build(self, left: Box<dyn AST>, right: Box<dyn AST>, span: Span) -> Box<dyn AST> {
+        match self {
+            BinaryOp::Concat => Box::new(OrExprAST::new(left, right, span)),
+            BinaryOp::Arithmetic(expr_type) => Box::new(ArithmeticExprAST::new(left, right, expr_type, span)),
+            BinaryOp::Equality(expr_type) => Box::new(EqualityExprAST::new(left, right, expr_type)),
+            BinaryOp::Logical(expr_type) => Box::new(LogicalExprAST::new(left, right, expr_type)),
+            BinaryOp::Range(inclusive) => Box::new(RangeAST::new(left, right, None, inclusive, span)),
+        }
     }
-    Ok((input, ast))
 }
 
-fn plus_minus_expr_ast(input: &str) -> IResult<&str, Box<dyn AST>> {
-    let (input, left) = or_expr_ast(input)?;
-
-    let result = preceded(
-        multispace0,
-        alt((tag("+"), tag("-")))
-    )(input);
-
-    if result.is_err() {
-        return Ok((input, left));
+/// Binary operators and how tightly they bind; a higher level binds tighter
+/// and every level is left associative. The unary `!` and `-` bind tighter
+/// than all of them.
+///
+/// | level | operators                        |
+/// |-------|----------------------------------|
+/// | 10    | `*` `/` `%`                      |
+/// | 9     | `+` `-`                          |
+/// | 8     | `<<` `>>`                        |
+/// | 7     | `&`                              |
+/// | 6     | `^`                              |
+/// | 5     | `..` `..=` (ranges)              |
+/// | 4     | `\|` (concatenation)            |
+/// | 3     | `==` `!=` `<` `>` `<=` `>=`      |
+/// | 2     | `&&`                             |
+/// | 1     | `\|\|`                           |
+///
+/// Longer tokens come first so `<<` is not read as `<`.
+const BINARY_OPS: &[(&str, u8, BinaryOp)] = &[
+    ("||", 1, BinaryOp::Logical(LogicalExprType::Or)),
+    ("&&", 2, BinaryOp::Logical(LogicalExprType::And)),
+    ("==", 3, BinaryOp::Equality(EqualityExprType::Eq)),
+    ("!=", 3, BinaryOp::Equality(EqualityExprType::Ne)),
+    ("<=", 3, BinaryOp::Equality(EqualityExprType::Le)),
+    (">=", 3, BinaryOp::Equality(EqualityExprType::Ge)),
+    ("<<", 8, BinaryOp::Arithmetic(ArithmeticExprType::Shl)),
+    (">>", 8, BinaryOp::Arithmetic(ArithmeticExprType::Shr)),
+    ("<", 3, BinaryOp::Equality(EqualityExprType::Lt)),
+    (">", 3, BinaryOp::Equality(EqualityExprType::Gt)),
+    ("|", 4, BinaryOp::Concat),
+    ("..=", 5, BinaryOp::Range(true)),
+    ("..", 5, BinaryOp::Range(false)),
+    ("^", 6, BinaryOp::Arithmetic(ArithmeticExprType::BitXor)),
+    ("&", 7, BinaryOp::Arithmetic(ArithmeticExprType::BitAnd)),
+    ("+", 9, BinaryOp::Arithmetic(ArithmeticExprType::Add)),
+    ("-", 9, BinaryOp::Arithmetic(ArithmeticExprType::Sub)),
+    ("*", 10, BinaryOp::Arithmetic(ArithmeticExprType::Mul)),
+    ("/", 10, BinaryOp::Arithmetic(ArithmeticExprType::Div)),
+    ("%", 10, BinaryOp::Arithmetic(ArithmeticExprType::Rem)),
+];
+
+fn binary_op(input: Input) -> PResult<(BinaryOp, u8, Span)> {
+    let (input, _) = ws(input)?;
+    for &(token, level, op) in BINARY_OPS {
+        if let Ok((rest, sign)) = tag::<_, _, SyntaxError>(token)(input) {
+            return Ok((rest, (op, level, span(&sign, &rest))));
+        }
     }
-
-    let (input, sign) = result?;
-
-    let expr_type = match sign {
-        "+" => Some(ArithmeticExprType::Add),
-        "-" => Some(ArithmeticExprType::Sub),
-        _ => None,
-    }.unwrap();
-
-    let (input, right) = or_expr_ast(input)?;
-
-    Ok((input, Box::new(ArithmeticExprAST::new(left, right, expr_type))))
+    Err(nom::Err::Error(SyntaxError::from_error_kind(input, ErrorKind::Tag)))
 }
 
-fn mul_expr_ast(input: &str) -> IResult<&str, Box<dyn AST>> {
-    let (input, _) = multispace0(input)?;
-
-    let (input, mut expr) = separated_list1(
-        tuple((multispace0, tag("*"), multispace0)),
-        plus_minus_expr_ast,
-    )(input)?;
-    expr.reverse();
-    let mut ast = expr.pop().unwrap();
-    while let Some(right) = expr.pop() {
-        ast = Box::new(ArithmeticExprAST::new(ast, right, ArithmeticExprType::Mul));
+fn unary_expr_ast(input: Input) -> PResult<Box<dyn AST>> {
+    let (input, _) = ws(input)?;
+    let start = input;
+    match alt((tag::<_, _, SyntaxError>("!"), tag("-")))(input) {
+        Ok((input, sign)) => {
+            let (input, expr) = operand(unary_expr_ast)(input)?;
+            match *sign.fragment() {
+                "!" => Ok((input, Box::new(NotExprAST::new(expr)))),
+                _ => Ok((input, Box::new(NegExprAST::new(expr, span(&start, &input))))),
+            }
+        }
+        Err(_) => value_expr_ast(input),
     }
-    Ok((input, ast))
 }
 
-pub fn equality_expr_ast(input: &str) -> IResult<&str, Box<dyn AST>> {
-    let (input, left) = mul_expr_ast(input)?;
-
-    let result = preceded(
-        multispace0,
-        alt((tag("=="), tag("!="), tag("<="), tag(">="), tag("<"), tag(">")))
-    )(input);
-
-    if result.is_err() {
-        return Ok((input, left));
+/// Precedence climbing: parses operands joined by operators of at least
+/// `min_level`, see `BINARY_OPS`. A range may be followed by `step` and an
+/// operand of the same level as its bounds.
+fn binary_expr_ast(input: Input, min_level: u8) -> PResult<Box<dyn AST>> {
+    let (mut input, mut left) = unary_expr_ast(input)?;
+    while let Ok((rest, (op, level, span))) = binary_op(input) {
+        if level < min_level {
+            break;
+        }
+        let (rest, right) = operand(|input| binary_expr_ast(input, level + 1))(rest)?;
+        input = rest;
+        left = match op {
+            BinaryOp::Range(inclusive) => match pair(ws, keyword("step"))(input) {
+                Ok((rest, _)) => {
+                    let (rest, step) = operand(|input| binary_expr_ast(input, level + 1))(rest)?;
+                    input = rest;
+                    Box::new(RangeAST::new(left, right, Some(step), inclusive, span))
+                }
+                Err(_) => op.build(left, right, span),
+            },
+            _ => op.build(left, right, span),
+        };
     }
+    Ok((input, left))
+}
 
-    let (input, sign) = result?;
-
-    let expr_type = match sign {
-        "==" => Some(EqualityExprType::Eq),
-        "!=" => Some(EqualityExprType::Ne),
-        "<" => Some(EqualityExprType::Lt),
-        ">" => Some(EqualityExprType::Gt),
-        "<=" => Some(EqualityExprType::Le),
-        ">=" => Some(EqualityExprType::Ge),
-        _ => None,
-    }.unwrap();
-
-    let (input, right) = mul_expr_ast(input)?;
-
-    Ok((input, Box::new(EqualityExprAST::new(left, right, expr_type))))
+pub fn operator_expr_ast(input: Input) -> PResult<Box<dyn AST>> {
+    binary_expr_ast(input, 0)
 }
 
-pub fn expr_ast(input: &str) -> IResult<&str, Box<dyn AST>> {
+pub fn expr_ast(input: Input) -> PResult<Box<dyn AST>> {
     return alt((
         var_assign_ast,
         var_ref_assign_ast,
-        equality_expr_ast,
+        operator_expr_ast,
     ))(input);
 }
 
-pub fn var_assign_ast(input: &str) -> IResult<&str, Box<dyn AST>> {
-    let (input, _) = multispace0(input)?;
+pub fn var_assign_ast(input: Input) -> PResult<Box<dyn AST>> {
+    let (input, _) = ws(input)?;
 
     let (input, var_name) = identifier(input)?;
-    let (input, _) = pair(multispace0, tag("="))(input)?;
+    let (input, _) = assign_sign(input)?;
 
-    let (input, value) = expr_ast(input)?;
+    let (input, value) = operand(expr_ast)(input)?;
     Ok((input, Box::new(VarAssignAST::new(var_name, value))))
 }
 
-pub fn var_ref_assign_ast(input: &str) -> IResult<&str, Box<dyn AST>> {
-    let (input, _) = multispace0(input)?;
+pub fn var_ref_assign_ast(input: Input) -> PResult<Box<dyn AST>> {
+    let (input, _) = ws(input)?;
 
+    let start = input;
     let (input, var_name) = identifier(input)?;
-    let (input, _) = pair(multispace0, tag("["))(input)?;
-    let (input, index) = expr_ast(input)?;
-    let (input, _) = pair(multispace0, tag("]"))(input)?;
-    let (input, _) = pair(multispace0, tag("="))(input)?;
+    let (input, indexs) = many1(index)(input)?;
+    let span = span(&start, &input);
+    let (input, _) = assign_sign(input)?;
 
-    let (input, value) = expr_ast(input)?;
-    Ok((input, Box::new(VarRefAssignAST::new(var_name, index, value))))
+    let (input, value) = operand(expr_ast)(input)?;
+    Ok((input, Box::new(VarRefAssignAST::new(var_name, indexs, value, span))))
 }
 
-fn var_def_ast(input: &str) -> IResult<&str, Box<dyn AST>> {
-    let (input, _) = multispace0(input)?;
+fn var_def_ast(input: Input) -> PResult<Box<dyn AST>> {
+    let (input, _) = ws(input)?;
 
     let (input, _) = tag("def")(input)?;
-    let (input, _) = multispace1(input)?;
+    let (input, _) = ws1(input)?;
     
     let (input, var_name) = identifier(input)?;
     Ok((input, Box::new(VarDefAST::new(var_name))))
 }
 
-fn func_def_ast(input: &str) -> IResult<&str, Box<dyn AST>> {
+fn func_def_ast(input: Input) -> PResult<Box<dyn AST>> {
+    let start = input;
     let (input, _) = tag("$")(input)?;
     
     let (input, func_name) = identifier(input)?;
+    let span = span(&start, &input);
+
+    let (input, _) = expect("(", "`(` after the function name")(input)?;
+    let (input, (args, body)) = func_body(input)?;
+    Ok((input, Box::new(FuncDefAST::new(func_name, args, body, span))))
+}
 
-    let (input, _) = tuple((multispace0, tag("("), multispace0))(input)?;
+/// `x, y) { ... }`, what follows the `(` of a function.
+fn func_body(input: Input) -> PResult<(Vec<String>, Box<dyn AST>)> {
+    let (input, _) = ws(input)?;
     let (input, args) = separated_list0(
-        tuple((multispace0, tag(","), multispace0)),
+        tuple((ws, tag(","), ws)),
         identifier,
     )(input)?;
-    let (input, _) = pair(multispace0, tag(")"))(input)?;
+    let (input, _) = expect(")", "`)` to close the parameter list")(input)?;
 
-    let (input, _) = pair(multispace0, tag("{"))(input)?;
+    let (input, _) = expect("{", "`{` to open the function body")(input)?;
     let (input, body) = block_ast(input)?;
-    let (input, _) = pair(multispace0, tag("}"))(input)?;
-    Ok((input, Box::new(FuncDefAST::new(func_name, args, body))))
+    let (input, _) = expect("}", "`}` to close the function body")(input)?;
+    Ok((input, (args, body)))
 }
 
-pub fn for_in_ast(input: &str) -> IResult<&str, Box<dyn AST>> {
-    let (input, _) = pair(multispace0, tag("@"))(input)?;
+// $(x) { ... }
+fn lambda_ast(input: Input) -> PResult<Box<dyn AST>> {
+    let (input, _) = pair(tag("$"), preceded(ws, tag("(")))(input)?;
+    let (input, (args, body)) = func_body(input)?;
+    Ok((input, Box::new(LambdaAST::new(args, body))))
+}
+
+/// `name` not followed by more identifier characters.
+fn keyword<'a>(name: &'static str) -> impl FnMut(Input<'a>) -> PResult<'a, Input<'a>> {
+    terminated(tag(name), not(take_while1(is_alpha_or_underscore)))
+}
 
-    let (input, _) = pair(multispace0, tag("("))(input)?;
+fn loop_var(input: Input) -> PResult<String> {
+    let (input, _) = ws(input)?;
     let (input, var_name) = identifier(input)?;
-    let (input, _) = pair(multispace0, tag(":"))(input)?;
+    // Not the `::` of a qualified name.
+    let (input, _) = pair(ws, terminated(tag(":"), not(tag(":"))))(input)?;
+    Ok((input, var_name))
+}
+
+// @(x : a) {} or @(a) {}
+pub fn for_in_ast(input: Input) -> PResult<Box<dyn AST>> {
+    let (input, _) = pair(ws, tag("@"))(input)?;
+
+    let (input, _) = expect("(", "`(` after `@`")(input)?;
+    let (input, var_name) = match loop_var(input) {
+        Ok((input, var_name)) => (input, Some(var_name)),
+        Err(_) => (input, None),
+    };
+    let (input, _) = ws(input)?;
+    let start = input;
     let (input, value) = expr_ast(input)?;
-    let (input, _) = pair(multispace0, tag(")"))(input)?;
+    let span = span(&start, &input);
+    let (input, _) = expect(")", "`)` to close the loop header")(input)?;
 
-    let (input, _) = pair(multispace0, tag("{"))(input)?;
+    let (input, _) = expect("{", "`{` to open the loop body")(input)?;
     let (input, body) = block_ast(input)?;
-    let (input, _) = pair(multispace0, tag("}"))(input)?;
-    Ok((input, Box::new(ForInAST::new(var_name, value, body))))
+    let (input, _) = expect("}", "`}` to close the loop body")(input)?;
+    match var_name {
+        Some(var_name) => Ok((input, Box::new(ForInAST::new(var_name, value, body, span)))),
+        None => Ok((input, Box::new(WhileAST::new(value, body)))),
+    }
+}
+
+fn break_ast(input: Input) -> PResult<Box<dyn AST>> {
+    let start = input;
+    let (input, _) = keyword("break")(input)?;
+    Ok((input, Box::new(BreakAST::new(span(&start, &input)))))
 }
 
-// ?=(a, b) {}
-pub fn if_eq_ast(input: &str) -> IResult<&str, Box<dyn AST>> {
-    let (input, _) = pair(multispace0, tag("?"))(input)?;
+fn return_ast(input: Input) -> PResult<Box<dyn AST>> {
+    let start = input;
+    let (input, _) = keyword("return")(input)?;
+    let span = span(&start, &input);
+    let (input, value) = match preceded(ws, tag::<_, _, SyntaxError>(";"))(input) {
+        Ok(_) => (input, None),
+        Err(_) => {
+            let (input, value) = expr_ast(input)?;
+            (input, Some(value))
+        }
+    };
+    Ok((input, Box::new(ReturnAST::new(value, span))))
+}
+
+fn continue_ast(input: Input) -> PResult<Box<dyn AST>> {
+    let start = input;
+    let (input, _) = keyword("continue")(input)?;
+    Ok((input, Box::new(ContinueAST::new(span(&start, &input)))))
+}
+
+fn import_path(input: Input) -> PResult<String> {
+    let (input, _) = ws(input)?;
+    let (input, path) = string_value(input)?;
+    let bytes: Vec<u8> = path.value.iter().map(|&b| b as u8).collect();
+    Ok((input, String::from_utf8_lossy(&bytes).to_string()))
+}
+
+// import "a.her" as a; or import f, g from "a.her";
+// Until the path or `from` is found, `import` may still be a variable.
+fn import_ast(input: Input) -> PResult<Box<dyn AST>> {
+    let start = input;
+    let (input, _) = keyword("import")(input)?;
+    if let Ok((input, names)) = terminated(
+        separated_list1(tuple((ws, tag(","), ws)), preceded(ws, identifier)),
+        pair(ws, keyword("from")),
+    )(input) {
+        let (input, path) = cut(context("a quoted file path", import_path))(input)?;
+        return Ok((input, Box::new(ImportAST::new(path, ImportKind::Names(names), span(&start, &input)))));
+    }
+    let (input, path) = import_path(input)?;
+    let (input, name) = match preceded(pair(ws, keyword("as")), preceded(ws, cut(context("a name after `as`", identifier))))(input) {
+        Ok((input, name)) => (input, Some(name)),
+        Err(nom::Err::Error(_)) => (input, None),
+        Err(e) => return Err(e),
+    };
+    Ok((input, Box::new(ImportAST::new(path, ImportKind::Qualified(name), span(&start, &input)))))
+}
 
-    let (input, _) = pair(multispace0, tag("("))(input)?;
+// ?(a) {} : ?(b) {} : {}
+pub fn if_eq_ast(input: Input) -> PResult<Box<dyn AST>> {
+    let (input, _) = pair(ws, tag("?"))(input)?;
+
+    let (input, _) = expect("(", "`(` after `?`")(input)?;
     let (input, value) = expr_ast(input)?;
-    let (input, _) = pair(multispace0, tag(")"))(input)?;
+    let (input, _) = expect(")", "`)` to close the condition")(input)?;
+
+    let (input, _) = expect("{", "`{` to open the branch body")(input)?;
+    let (input, body) = block_ast(input)?;
+    let (input, _) = expect("}", "`}` to close the branch body")(input)?;
+
+    let (input, else_body) = match preceded(ws, tag::<_, _, SyntaxError>(":"))(input) {
+        Ok((input, _)) => {
+            let (input, else_body) = cut(context("`?` or `{` after `:`", alt((if_eq_ast, else_ast))))(input)?;
+            (input, Some(else_body))
+        }
+        Err(_) => (input, None),
+    };
+    Ok((input, Box::new(IfAST::new(value, body, else_body))))
+}
 
-    let (input, _) = pair(multispace0, tag("{"))(input)?;
+fn else_ast(input: Input) -> PResult<Box<dyn AST>> {
+    let (input, _) = pair(ws, tag("{"))(input)?;
     let (input, body) = block_ast(input)?;
-    let (input, _) = pair(multispace0, tag("}"))(input)?;
-    Ok((input, Box::new(IfAST::new(value, body))))
+    let (input, _) = expect("}", "`}` to close the branch body")(input)?;
+    Ok((input, body))
 }
 
-pub fn statement_ast(input: &str) -> IResult<&str, Box<dyn AST>> {
-    let (input, _) = multispace0(input)?;
+pub fn statement_ast(input: Input) -> PResult<Box<dyn AST>> {
+    let (input, _) = ws(input)?;
 
     let (input, statement) = alt((
         var_def_ast,
+        break_ast,
+        continue_ast,
+        return_ast,
+        import_ast,
         expr_ast,
         func_def_ast,
         if_eq_ast,
         for_in_ast,
     ))(input)?;
     let statement = Box::new(StatementAST::new(statement));
-    let (input, _) = pair(multispace0, tag(";"))(input)?;
+    let (input, _) = expect(";", "`;` after the statement")(input)?;
     Ok((input, statement))
 }
 
-pub fn block_ast(input: &str) -> IResult<&str, Box<dyn AST>> {
-    let (input, _) = multispace0(input)?;
-    let (input, statements) = many0(
-        statement_ast,
-    )(input)?;
+fn statement_list(mut input: Input) -> PResult<Vec<Box<dyn AST>>> {
+    let mut statements = Vec::new();
+    loop {
+        input = match ws(input) {
+            Ok((rest, _)) => rest,
+            Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+                // Only an unterminated block comment, which runs to the end.
+                input.extra.report(e.clone().into());
+                e.input
+            }
+            Err(e) => return Err(e),
+        };
+        if input.fragment().is_empty() || input.fragment().starts_with('}') {
+            return Ok((input, statements));
+        }
+        match statement_ast(input) {
+            Ok((rest, statement)) => {
+                statements.push(statement);
+                input = rest;
+            }
+            Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+                input.extra.report(e.clone().into());
+                input = skip_statement(e.input);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+pub fn block_ast(input: Input) -> PResult<Box<dyn AST>> {
+    let (input, statements) = statement_list(input)?;
     Ok((input, Box::new(BlockAST::new(statements))))
 }
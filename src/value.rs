@@ -1,19 +1,361 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::fmt;
-use std::ops::{Add, Sub, Mul};
+use std::ops::{Add, Sub, Mul, Div, Rem, BitAnd, BitXor, Shl, Shr, Neg};
+use std::rc::Rc;
 
-#[derive(Debug, Clone, PartialEq, PartialOrd)]
+use crate::ast::Func;
+
+/// What a `Value` holds besides numbers.
+#[derive(Debug, Clone)]
+pub enum Object {
+    Func(Box<dyn Func>),
+    /// A list of values, each of which may be a list again.
+    List(Vec<Value>),
+    /// Values keyed by numbers, in the order of their keys.
+    Map(BTreeMap<Vec<u32>, Value>),
+}
+
+/// A sequence of numbers, or an object such as a function, a list or a
+/// map, in which case `value` is empty.
+#[derive(Debug, Clone, Default)]
 pub struct Value {
     pub value: Vec<u32>,
+    pub object: Option<Rc<Object>>,
 }
 
 impl Value {
     pub fn new(value: Vec<u32>) -> Self {
-        Self { value }
+        Self { value, object: None }
+    }
+
+    pub fn func(func: Box<dyn Func>) -> Self {
+        Self { value: Vec::new(), object: Some(Rc::new(Object::Func(func))) }
+    }
+
+    pub fn list(items: Vec<Value>) -> Self {
+        Self { value: Vec::new(), object: Some(Rc::new(Object::List(items))) }
+    }
+
+    pub fn map(entries: BTreeMap<Vec<u32>, Value>) -> Self {
+        Self { value: Vec::new(), object: Some(Rc::new(Object::Map(entries))) }
+    }
+
+    pub fn as_func(&self) -> Option<&(dyn Func + 'static)> {
+        match self.object.as_deref() {
+            Some(Object::Func(func)) => Some(func.as_ref()),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&Vec<Value>> {
+        match self.object.as_deref() {
+            Some(Object::List(items)) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// The items of a list, copied on write if the list is shared.
+    pub fn as_list_mut(&mut self) -> Option<&mut Vec<Value>> {
+        self.as_list()?;
+        match self.object.as_mut().map(Rc::make_mut) {
+            Some(Object::List(items)) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn as_map(&self) -> Option<&BTreeMap<Vec<u32>, Value>> {
+        match self.object.as_deref() {
+            Some(Object::Map(entries)) => Some(entries),
+            _ => None,
+        }
+    }
+
+    /// The entries of a map, copied on write if the map is shared.
+    pub fn as_map_mut(&mut self) -> Option<&mut BTreeMap<Vec<u32>, Value>> {
+        self.as_map()?;
+        match self.object.as_mut().map(Rc::make_mut) {
+            Some(Object::Map(entries)) => Some(entries),
+            _ => None,
+        }
+    }
+
+    /// Names what the value holds, for error messages.
+    pub fn kind(&self) -> &'static str {
+        match self.object.as_deref() {
+            Some(Object::Func(_)) => "function",
+            Some(Object::List(_)) => "list",
+            Some(Object::Map(_)) => "map",
+            None => "number",
+        }
+    }
+
+    /// The number of items of a list, entries of a map, or elements
+    /// otherwise.
+    pub fn len(&self) -> usize {
+        match self.object.as_deref() {
+            Some(Object::List(items)) => items.len(),
+            Some(Object::Map(entries)) => entries.len(),
+            _ => self.value.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// What `@` iterates over: the items of a list, the keys of a map, or
+    /// every element of a number on its own. `None` for a function.
+    pub fn items(&self) -> Option<Vec<Value>> {
+        match self.object.as_deref() {
+            Some(Object::List(items)) => Some(items.clone()),
+            Some(Object::Map(entries)) => Some(entries.keys().map(|key| Value::new(key.clone())).collect()),
+            Some(Object::Func(_)) => None,
+            None => Some(self.value.iter().map(|&x| Value::new(vec![x])).collect()),
+        }
+    }
+
+    /// `|`: concatenates numbers, or lists, in which case an operand that is
+    /// not a list is an item on its own. `None` when a function or a map
+    /// would be concatenated with a number.
+    pub fn concat(self, rhs: Self) -> Option<Self> {
+        if self.as_list().is_none() && rhs.as_list().is_none() {
+            if self.object.is_some() || rhs.object.is_some() {
+                return None;
+            }
+            let mut value = self.value;
+            value.extend(rhs.value);
+            return Some(Self::new(value));
+        }
+        let into_items = |value: Value| match value.as_list() {
+            Some(items) => items.clone(),
+            None => vec![value],
+        };
+        let mut items = into_items(self);
+        items.extend(into_items(rhs));
+        Some(Self::list(items))
+    }
+
+    /// `/` on sign-extended elements, see `Numeric::extend`.
+    pub fn signed_div(self, rhs: Self) -> Result<Self, ArithmeticError> {
+        self.zip_with(&rhs, |x, y| (y != 0).then(|| (x as i32).wrapping_div(y as i32) as u32))
+    }
+
+    pub fn signed_rem(self, rhs: Self) -> Result<Self, ArithmeticError> {
+        self.zip_with(&rhs, |x, y| (y != 0).then(|| (x as i32).wrapping_rem(y as i32) as u32))
+    }
+
+    /// `>>` that fills with the sign bit.
+    pub fn signed_shr(self, rhs: Self) -> Result<Self, ArithmeticError> {
+        self.zip_with(&rhs, |x, y| Some(((x as i32) >> y.min(31)) as u32))
+    }
+
+    /// Applies `f` element by element. The operands must have the same
+    /// length, except that a single-element operand is repeated to match
+    /// the other one. `f` returns `None` for a division by zero.
+    fn zip_with(&self, rhs: &Self, f: impl Fn(u32, u32) -> Option<u32>) -> Result<Self, ArithmeticError> {
+        let (left, right) = (&self.value, &rhs.value);
+        let len = match (left.len(), right.len()) {
+            (l, r) if l == r => l,
+            (1, r) => r,
+            (l, 1) => l,
+            (l, r) => return Err(ArithmeticError::LengthMismatch { left: l, right: r }),
+        };
+        let at = |value: &Vec<u32>, i: usize| value[if value.len() == 1 { 0 } else { i }];
+        (0..len)
+            .map(|i| f(at(left, i), at(right, i)).ok_or(ArithmeticError::DivisionByZero))
+            .collect::<Result<Vec<u32>, ArithmeticError>>()
+            .map(Self::new)
+    }
+}
+
+/// How wide the elements of a `Value` are and whether they are signed.
+/// Arithmetic wraps around at this width, like the `u8` everything is meant
+/// to be; signed elements are stored in two's complement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Numeric {
+    bits: u32,
+    signed: bool,
+}
+
+impl Numeric {
+    pub const WIDTHS: [u32; 3] = [8, 16, 32];
+
+    /// Unsigned, `None` unless `bits` is one of `WIDTHS`.
+    pub fn new(bits: u32) -> Option<Self> {
+        Self::WIDTHS.contains(&bits).then_some(Self { bits, signed: false })
+    }
+
+    pub fn with_signed(self, signed: bool) -> Self {
+        Self { signed, ..self }
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    pub fn is_signed(&self) -> bool {
+        self.signed
+    }
+
+    fn mask(&self) -> u32 {
+        u32::MAX >> (32 - self.bits)
+    }
+
+    /// The largest number an element can hold, which is also the largest
+    /// literal allowed.
+    pub fn max(&self) -> i64 {
+        if self.signed {
+            (self.mask() >> 1) as i64
+        } else {
+            self.mask() as i64
+        }
+    }
+
+    /// The smallest number an element can hold.
+    pub fn min(&self) -> i64 {
+        if self.signed {
+            -self.max() - 1
+        } else {
+            0
+        }
+    }
+
+    pub fn fits(&self, x: u32) -> bool {
+        x as i64 <= self.max()
+    }
+
+    /// Keeps the low `bits` of every element.
+    pub fn wrap(&self, value: Value) -> Value {
+        Value::new(value.value.into_iter().map(|x| x & self.mask()).collect())
+    }
+
+    /// The number an element stands for.
+    pub fn number(&self, x: u32) -> i64 {
+        let x = x & self.mask();
+        if self.signed && x as i64 > self.max() {
+            x as i64 - self.mask() as i64 - 1
+        } else {
+            x as i64
+        }
+    }
+
+    /// Sign-extends every element to 32 bits, so the operators on `Value`
+    /// see negative numbers as such.
+    pub fn extend(&self, value: Value) -> Value {
+        Value::new(value.value.into_iter().map(|x| self.number(x) as u32).collect())
+    }
+
+    /// Orders values element by element, like `Vec` does, by the numbers
+    /// their elements stand for. Lists are ordered item by item and maps
+    /// entry by entry; numbers come before lists, then maps, then functions.
+    pub fn compare(&self, left: &Value, right: &Value) -> Ordering {
+        let rank = |value: &Value| match value.object.as_deref() {
+            None => 0,
+            Some(Object::List(_)) => 1,
+            Some(Object::Map(_)) => 2,
+            Some(Object::Func(_)) => 3,
+        };
+        let numbers = |x: &[u32], y: &[u32]| x.iter().map(|&a| self.number(a))
+            .cmp(y.iter().map(|&b| self.number(b)));
+        match (left.object.as_deref(), right.object.as_deref()) {
+            (Some(Object::List(x)), Some(Object::List(y))) => x.iter().zip(y.iter())
+                .map(|(a, b)| self.compare(a, b))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or_else(|| x.len().cmp(&y.len())),
+            (Some(Object::Map(x)), Some(Object::Map(y))) => x.iter().zip(y.iter())
+                .map(|((k, a), (l, b))| numbers(k, l).then_with(|| self.compare(a, b)))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or_else(|| x.len().cmp(&y.len())),
+            (None, None) => numbers(&left.value, &right.value),
+            _ => rank(left).cmp(&rank(right)),
+        }
+    }
+
+    /// Like the `Display` of `Value`, with negative numbers in signed mode.
+    pub fn format(&self, value: &Value) -> String {
+        if let Some(items) = value.as_list() {
+            let items: Vec<String> = items.iter().map(|item| self.format(item)).collect();
+            return format!("[{}]", items.join(", "));
+        }
+        if let Some(entries) = value.as_map() {
+            let entries: Vec<String> = entries.iter()
+                .map(|(key, item)| format!("{}: {}", self.format(&Value::new(key.clone())), self.format(item)))
+                .collect();
+            return format!("{{{}}}", entries.join(", "));
+        }
+        if value.object.is_some() {
+            return value.to_string();
+        }
+        let numbers: Vec<String> = value.value.iter()
+            .map(|&x| self.number(x).to_string())
+            .collect();
+        format!("({})", numbers.join(" | "))
     }
 }
 
+impl Default for Numeric {
+    fn default() -> Self {
+        Self { bits: 8, signed: false }
+    }
+}
+
+/// `u8`, `i16` and so on.
+impl fmt::Display for Numeric {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", if self.signed { 'i' } else { 'u' }, self.bits)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArithmeticError {
+    /// `/` or `%` with a 0 on the right-hand side.
+    DivisionByZero,
+    /// Operands of different lengths, neither of which has a single element.
+    LengthMismatch { left: usize, right: usize },
+}
+
+/// Functions are only equal to themselves, lists and maps are equal item by
+/// item.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value && match (&self.object, &other.object) {
+            (Some(x), Some(y)) => Rc::ptr_eq(x, y) || match (x.as_ref(), y.as_ref()) {
+                (Object::List(x), Object::List(y)) => x == y,
+                (Object::Map(x), Object::Map(y)) => x == y,
+                _ => false,
+            },
+            (x, y) => x.is_none() && y.is_none(),
+        }
+    }
+}
+
+/// Lists print as `[(1 | 2), (3)]`, maps as `{(1): (2), (3 | 4): [(5)]}`.
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.as_func().is_some() {
+            return write!(f, "<function>");
+        }
+        if let Some(items) = self.as_list() {
+            write!(f, "[")?;
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", item)?;
+            }
+            return write!(f, "]");
+        }
+        if let Some(entries) = self.as_map() {
+            write!(f, "{{")?;
+            for (i, (key, item)) in entries.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}: {}", Value::new(key.clone()), item)?;
+            }
+            return write!(f, "}}");
+        }
         write!(f, "(")?;
         if !self.value.is_empty() {
             let mut first = true;
@@ -31,15 +373,12 @@ impl fmt::Display for Value {
     }
 }
 
-impl Default for Value {
-    fn default() -> Self {
-        Self { value: vec![] }
-    }
-}
-
-impl Into<bool> for Value {
-    fn into(self) -> bool {
-        !self.value.is_empty() && self.value.iter().all(|&v| v != 0)
+impl From<Value> for bool {
+    fn from(value: Value) -> Self {
+        if value.as_list().is_some() || value.as_map().is_some() {
+            return !value.is_empty();
+        }
+        value.object.is_some() || (!value.value.is_empty() && value.value.iter().all(|&v| v != 0))
     }
 }
 
@@ -55,38 +394,85 @@ impl From<bool> for Value {
     }
 }
 
+/// Arithmetic wraps around at 32 bits; `Numeric::wrap` narrows the result
+/// to the configured width.
 impl Add for Value {
-    type Output = Self;
+    type Output = Result<Self, ArithmeticError>;
 
     fn add(self, rhs: Self) -> Self::Output {
-        Self::new(
-            self.value.iter().zip(rhs.value.iter())
-                .map(|(x, y)| x + y)
-                .collect()
-        )
+        self.zip_with(&rhs, |x, y| Some(x.wrapping_add(y)))
     }
 }
 
 impl Sub for Value {
-    type Output = Self;
+    type Output = Result<Self, ArithmeticError>;
 
     fn sub(self, rhs: Self) -> Self::Output {
-        Self::new(
-            self.value.iter().zip(rhs.value.iter())
-                .map(|(x, y)| x - y)
-                .collect()
-        )
+        self.zip_with(&rhs, |x, y| Some(x.wrapping_sub(y)))
     }
 }
 
 impl Mul for Value {
-    type Output = Self;
+    type Output = Result<Self, ArithmeticError>;
 
     fn mul(self, rhs: Self) -> Self::Output {
-        Self::new(
-            self.value.iter().zip(rhs.value.iter())
-                .map(|(x, y)| x * y)
-                .collect()
-        )
+        self.zip_with(&rhs, |x, y| Some(x.wrapping_mul(y)))
+    }
+}
+
+impl Div for Value {
+    type Output = Result<Self, ArithmeticError>;
+
+    fn div(self, rhs: Self) -> Self::Output {
+        self.zip_with(&rhs, u32::checked_div)
+    }
+}
+
+impl Rem for Value {
+    type Output = Result<Self, ArithmeticError>;
+
+    fn rem(self, rhs: Self) -> Self::Output {
+        self.zip_with(&rhs, u32::checked_rem)
+    }
+}
+
+impl BitAnd for Value {
+    type Output = Result<Self, ArithmeticError>;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        self.zip_with(&rhs, |x, y| Some(x & y))
+    }
+}
+
+impl BitXor for Value {
+    type Output = Result<Self, ArithmeticError>;
+
+    fn bitxor(self, rhs: Self) -> Self::Output {
+        self.zip_with(&rhs, |x, y| Some(x ^ y))
+    }
+}
+
+/// Shifting by the width of an element or more gives 0.
+impl Shl for Value {
+    type Output = Result<Self, ArithmeticError>;
+
+    fn shl(self, rhs: Self) -> Self::Output {
+        self.zip_with(&rhs, |x, y| Some(x.checked_shl(y).unwrap_or(0)))
+    }
+}
+
+impl Shr for Value {
+    type Output = Result<Self, ArithmeticError>;
+
+    fn shr(self, rhs: Self) -> Self::Output {
+        self.zip_with(&rhs, |x, y| Some(x.checked_shr(y).unwrap_or(0)))
+    }
+}
+
+impl Neg for Value {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        Self::new(self.value.into_iter().map(u32::wrapping_neg).collect())
     }
 }
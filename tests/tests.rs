@@ -1,4 +1,34 @@
-use herang::{init_env, eval};
+use herang::{init_env, eval, gen_code, parse_all, check, render, HeError, Span, Arity, Numeric, Value};
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Translates `input` to C++, builds it with `example/cpp_header.cpp` and
+/// returns what it prints, or `None` where there is no `g++`.
+fn run_cpp(name: &str, input: &str, env: &mut herang::HeEnv) -> Option<String> {
+    let code = gen_code(input, env).unwrap();
+    let header = fs::read_to_string("example/cpp_header.cpp").unwrap();
+    let header = &header[..header.find("int main() {").unwrap()];
+    let dir = std::env::temp_dir().join(format!("herang_{}_{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let source = dir.join("main.cpp");
+    fs::write(&source, format!("{}int main() {{\n{}return 0;\n}}\n", header, code)).unwrap();
+
+    let numeric = env.numeric();
+    let binary = dir.join("main");
+    let status = Command::new("g++")
+        .args(["-std=c++17", "-w", "-o"])
+        .arg(&binary)
+        .arg(format!("-DHERANG_BITS={}", numeric.bits()))
+        .arg(format!("-DHERANG_SIGNED={}", numeric.is_signed() as u8))
+        .arg(&source)
+        .status()
+        .ok()?;
+    assert!(status.success(), "{} does not compile", source.display());
+    let output = Command::new(&binary).output().unwrap();
+    assert!(output.status.success(), "{} failed: {}", binary.display(), String::from_utf8_lossy(&output.stderr));
+    Some(String::from_utf8(output.stdout).unwrap())
+}
 
 #[test]
 fn test_assign() {
@@ -35,13 +65,75 @@ fn test_arithmetic_expr() {
     {
         let mut env = herang::HeEnv::new();
         init_env(&mut env).unwrap();
-        let result = eval("a = 4 | 2 + 0 | 1;", &mut env);
+        let result = eval("a = (4 | 2) + (0 | 1);", &mut env);
         assert_eq!(result.unwrap().value, vec![4, 3]);
-        let result = eval("a = 4 | 2 - 2 | 1;", &mut env);
+        let result = eval("a = (4 | 2) - (2 | 1);", &mut env);
         assert_eq!(result.unwrap().value, vec![2, 1]);
-        let result = eval("a = 4 | 2 * 2 | 3;", &mut env);
+        let result = eval("a = (4 | 2) * (2 | 3);", &mut env);
         assert_eq!(result.unwrap().value, vec![8, 6]);
+        let result = eval("a = (17 | 9) / (5 | 2);", &mut env);
+        assert_eq!(result.unwrap().value, vec![3, 4]);
+        let result = eval("a = (17 | 9) % (5 | 2);", &mut env);
+        assert_eq!(result.unwrap().value, vec![2, 1]);
+        let result = eval("a = (12 | 12) & (10 | 3);", &mut env);
+        assert_eq!(result.unwrap().value, vec![8, 0]);
+        let result = eval("a = (12 | 12) ^ (10 | 3);", &mut env);
+        assert_eq!(result.unwrap().value, vec![6, 15]);
+        let result = eval("a = (1 | 3) << (4 | 32);", &mut env);
+        assert_eq!(result.unwrap().value, vec![16, 0]);
+        let result = eval("a = 255 >> 4;", &mut env);
+        assert_eq!(result.unwrap().value, vec![15]);
     }
+    {
+        let mut env = herang::HeEnv::new();
+        init_env(&mut env).unwrap();
+        let result = eval("a = 4 | 2;\nb = a % (1 | 0);", &mut env);
+        assert_eq!(result.unwrap_err(), HeError::DivisionByZero { span: Span::new(17, 18) });
+    }
+}
+
+#[test]
+fn test_precedence() {
+    let mut env = herang::HeEnv::new();
+    init_env(&mut env).unwrap();
+
+    let result = eval("10 - 3 - 2;", &mut env);
+    assert_eq!(result.unwrap().value, vec![5]);
+    let result = eval("100 / 5 / 2 % 7;", &mut env);
+    assert_eq!(result.unwrap().value, vec![3]);
+    let result = eval("1 + 2 * 3 | 4;", &mut env);
+    assert_eq!(result.unwrap().value, vec![7, 4]);
+    let result = eval("1 << 2 + 1;", &mut env);
+    assert_eq!(result.unwrap().value, vec![8]);
+    let result = eval("6 ^ 3 & 5 | 1 << 1;", &mut env);
+    assert_eq!(result.unwrap().value, vec![7, 2]);
+    let result = eval("1 | 2 == 1 | 2;", &mut env);
+    assert_eq!(result.unwrap().value, vec![1]);
+    let result = eval("3 > 2 > 1;", &mut env);
+    assert_eq!(result.unwrap().value, vec![0]);
+    let result = eval("a = 1 + 1 | 3 - 1;", &mut env);
+    assert_eq!(result.unwrap().value, vec![2, 2]);
+}
+
+#[test]
+fn test_logical() {
+    let mut env = herang::HeEnv::new();
+    init_env(&mut env).unwrap();
+
+    let result = eval("(1 && 2) | (1 && 0) | (0 || 3) | (0 || 0);", &mut env);
+    assert_eq!(result.unwrap().value, vec![1, 0, 1, 0]);
+    let result = eval("!0 | !(1 | 2) | !!5 | !(1 | 0);", &mut env);
+    assert_eq!(result.unwrap().value, vec![1, 0, 1, 1]);
+    let result = eval("1 < 2 && 3 < 2 || 1 == 1;", &mut env);
+    assert_eq!(result.unwrap().value, vec![1]);
+
+    // The right side is skipped once the left side decides the result.
+    let result = eval("0 && missing;", &mut env);
+    assert_eq!(result.unwrap().value, vec![0]);
+    let result = eval("a = 0; 1 || (a = 5); 0 && (a = 6); a;", &mut env);
+    assert_eq!(result.unwrap().value, vec![0]);
+    let result = eval("1 && missing;", &mut env);
+    assert!(matches!(result, Err(HeError::UndefinedVariable { .. })));
 }
 
 #[test]
@@ -235,3 +327,710 @@ fn test_var_def() {
     let result = eval("result;", &mut env);
     assert_eq!(result.unwrap().value, vec![3]);
 }
+
+#[test]
+fn test_error() {
+    let mut env = herang::HeEnv::new();
+    init_env(&mut env).unwrap();
+
+    let result = eval("a = 1;\nb = a | c;", &mut env);
+    assert_eq!(result.unwrap_err(), HeError::UndefinedVariable {
+        name: "c".to_string(),
+        span: Span::new(15, 16),
+    });
+
+    let result = eval("foo(1);", &mut env);
+    assert_eq!(result.unwrap_err(), HeError::UndefinedFunction {
+        name: "foo".to_string(),
+        span: Span::new(0, 6),
+    });
+
+    let result = eval("len(1, 2);", &mut env);
+    assert_eq!(result.unwrap_err(), HeError::ArityMismatch {
+        name: "len".to_string(),
+        expected: Arity::Exact(1),
+        got: 2,
+        span: Span::new(0, 9),
+    });
+
+    let result = eval("a = 1 | 2; a[2];", &mut env);
+    let err = result.unwrap_err();
+    assert_eq!(err, HeError::IndexOutOfRange { index: 2, len: 2, span: Span::new(11, 15) });
+    assert_eq!(err.span().line_col("a = 1 | 2; a[2];"), (1, 12));
+
+    let result = eval("a = 1;\n  b = ;", &mut env);
+    let err = result.unwrap_err();
+    assert!(matches!(err, HeError::ParseError { .. }));
+    assert_eq!(err.span().line_col("a = 1;\n  b = ;"), (2, 7));
+}
+
+#[test]
+fn test_diagnostic() {
+    let mut env = herang::HeEnv::new();
+    init_env(&mut env).unwrap();
+
+    let source = "a = 1;\n$f(x) { x | a;\n";
+    let err = eval(source, &mut env).unwrap_err();
+    assert_eq!(render(&err, "f.her", source), concat!(
+        "error: expected `}` to close the function body, found end of input\n",
+        " --> f.her:3:1\n",
+        "  |\n",
+        "3 | \n",
+        "  | ^",
+    ));
+
+    let source = "a = 1 | 2\nb = 3;";
+    let err = eval(source, &mut env).unwrap_err();
+    assert_eq!(err.to_string(), "expected `;` after the statement, found `b`");
+
+    let source = "a = 1;\nprint(a[0 | 4]);";
+    let err = eval(source, &mut env).unwrap_err();
+    assert_eq!(render(&err, "f.her", source), concat!(
+        "error: Index 4 out of range(length 1)\n",
+        " --> f.her:2:7\n",
+        "  |\n",
+        "2 | print(a[0 | 4]);\n",
+        "  |       ^^^^^^^^ valid indexs are 0 to 0",
+    ));
+
+    let source = "a = cyber(0);\na[0];";
+    let err = eval(source, &mut env).unwrap_err();
+    assert!(render(&err, "f.her", source).ends_with("^^^^ the sequence is empty"));
+}
+
+#[test]
+fn test_recover() {
+    let source = concat!(
+        "a = 1 | 2;\n",
+        "b = ;\n",
+        "$f(x) { x = (1; x | 3; };\n",
+        "};\n",
+        "c = f(a);\n",
+    );
+    let output = parse_all(source);
+    let errors = output.errors;
+    let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+    assert_eq!(messages, vec![
+        "expected an expression after the operator, found `;`",
+        "expected `)` to close the parenthesis, found `;`",
+        "unexpected `}`",
+    ]);
+    assert_eq!(errors[2].span().line_col(source), (4, 1));
+
+    let mut env = herang::HeEnv::new();
+    init_env(&mut env).unwrap();
+    let result = output.ast.eval(&mut env);
+    assert_eq!(result.unwrap().value, vec![1, 2, 3]);
+
+    assert!(check("a = 1; b = a;").is_empty());
+    assert_eq!(check("a = 1 b = 2; $f() {").len(), 2);
+}
+
+#[test]
+fn test_trace() {
+    let mut env = herang::HeEnv::new();
+    init_env(&mut env).unwrap();
+
+    let source = concat!(
+        "$pop(s) { slice(s, 0, len(s) + 1); };\n",
+        "$reverse(s) {\n",
+        "    ?(len(s) > 1) { s = reverse(pop(s)); };\n",
+        "    s;\n",
+        "};\n",
+        "reverse(1 | 2 | 3);\n",
+    );
+    let err = eval(source, &mut env).unwrap_err();
+    assert!(matches!(err.root(), HeError::IndexOutOfRange { index: 4, len: 3, .. }));
+    let names: Vec<&str> = err.trace().iter().map(|frame| frame.name.as_str()).collect();
+    assert_eq!(names, vec!["reverse", "pop"]);
+    assert_eq!(
+        err.trace_message(source).unwrap(),
+        "in pop (line 1) <- called from reverse (line 3) <- called from main (line 6)",
+    );
+    assert!(env.call_stack().is_empty());
+
+    let err = eval("slice(1, 0, 2);", &mut env).unwrap_err();
+    assert!(err.trace().is_empty());
+}
+
+#[test]
+fn test_if_else() {
+    let mut env = herang::HeEnv::new();
+    init_env(&mut env).unwrap();
+
+    let result = eval("$level(x) { ?(x < 10) { 0; } : ?(x < 20) { 1; } : { 2; }; };", &mut env);
+    assert!(result.is_ok());
+    let result = eval("level(5) | level(15) | level(25);", &mut env);
+    assert_eq!(result.unwrap().value, vec![0, 1, 2]);
+
+    let result = eval("a = 0; ?(a) { a = 1; } : { a = 2; };", &mut env);
+    assert_eq!(result.unwrap().value, vec![2]);
+    let result = eval("?(a) { a = a | 3; } : { a = 4; };", &mut env);
+    assert_eq!(result.unwrap().value, vec![2, 3]);
+
+    let result = eval("?(0) { 1; } : ?(0) { 2; };", &mut env);
+    assert_eq!(result.unwrap().value, Vec::<u32>::new());
+}
+
+#[test]
+fn test_while() {
+    let mut env = herang::HeEnv::new();
+    init_env(&mut env).unwrap();
+
+    let input = concat!(
+        "n = 0; result = cyber(0);",
+        "@(n < 10) {",
+        "    n = n + 1;",
+        "    ?(n == 3) { continue; };",
+        "    ?(n == 6) { break; };",
+        "    result = result | n;",
+        "};",
+    );
+    let result = eval(input, &mut env);
+    assert!(result.is_ok());
+    let result = eval("result;", &mut env);
+    assert_eq!(result.unwrap().value, vec![1, 2, 4, 5]);
+
+    let input = concat!(
+        "result = cyber(0);",
+        "@(i : 1 | 2 | 3 | 4) {",
+        "    @(j : 1 | 2 | 3) {",
+        "        ?(j == 2) { continue; };",
+        "        ?(i == 3) { break; };",
+        "        result = result | i;",
+        "    };",
+        "};",
+    );
+    let result = eval(input, &mut env);
+    assert!(result.is_ok());
+    let result = eval("result;", &mut env);
+    assert_eq!(result.unwrap().value, vec![1, 1, 2, 2, 4, 4]);
+
+    let result = eval("break;", &mut env);
+    assert!(matches!(result.unwrap_err(), HeError::Break { .. }));
+    let result = eval("$f() { continue; }; @(i : 1) { f(); };", &mut env);
+    assert!(matches!(result.unwrap_err().root(), HeError::Continue { .. }));
+    let result = eval("breakfast = 1;", &mut env);
+    assert_eq!(result.unwrap().value, vec![1]);
+
+    // In C++ they cannot leave the function to end the caller's loop.
+    let mut env = herang::HeEnv::new();
+    init_env(&mut env).unwrap();
+    let result = gen_code("$f() { break; }; @(i : 1 | 2 | 3) { print(i); f(); };", &mut env);
+    assert_eq!(result.unwrap_err(), HeError::Break { span: Span::new(7, 12) });
+    let result = gen_code("@(i : 1) { g = $() { continue; }; };", &mut env);
+    assert!(matches!(result.unwrap_err(), HeError::Continue { .. }));
+    assert!(gen_code("$h() { @(i : 1) { break; }; };", &mut env).is_ok());
+}
+
+#[test]
+fn test_return() {
+    let mut env = herang::HeEnv::new();
+    init_env(&mut env).unwrap();
+
+    let input = concat!(
+        "$reverse(s) {",
+        "    ?(len(s) <= 1) { return s; };",
+        "    s[len(s) - 1] | reverse(slice(s, 1, len(s) - 1)) | s[0];",
+        "};",
+        "$find(s, x) {",
+        "    n = 0;",
+        "    @(n < len(s)) {",
+        "        @(i : s[n]) { ?(i == x) { return n; }; };",
+        "        n = n + 1;",
+        "    };",
+        "    len(s);",
+        "};",
+    );
+    let result = eval(input, &mut env);
+    assert!(result.is_ok());
+    let result = eval("reverse(1 | 2 | 3 | 4);", &mut env);
+    assert_eq!(result.unwrap().value, vec![4, 3, 2, 1]);
+    let result = eval("find(5 | 6 | 7, 6) | find(5 | 6 | 7, 8);", &mut env);
+    assert_eq!(result.unwrap().value, vec![1, 3]);
+
+    let result = eval("$f() { return; 1; }; f();", &mut env);
+    assert_eq!(result.unwrap().value, Vec::<u32>::new());
+    let result = eval("return 1;", &mut env);
+    assert!(matches!(result.unwrap_err(), HeError::Return { .. }));
+
+    let mut env = herang::HeEnv::new();
+    init_env(&mut env).unwrap();
+    let result = gen_code("x = 1; ?(x) { return 1; };", &mut env);
+    assert!(matches!(result.unwrap_err(), HeError::Return { .. }));
+    assert!(gen_code("$g() { ?(1) { return 1; }; };", &mut env).is_ok());
+}
+
+#[test]
+fn test_comment() {
+    let mut env = herang::HeEnv::new();
+    init_env(&mut env).unwrap();
+
+    let source = concat!(
+        "// line comment\n",
+        "a = 1 | /* inline */ 2; // trailing\n",
+        "/* block; }\n   comment */\n",
+        "$f(x) { x | 3; };\n",
+        "f(a);",
+    );
+    let result = eval(source, &mut env);
+    assert_eq!(result.unwrap().value, vec![1, 2, 3]);
+
+    let comments: Vec<String> = parse_all(source).comments.into_iter()
+        .map(|comment| comment.text)
+        .collect();
+    assert_eq!(comments, vec![
+        "// line comment",
+        "/* inline */",
+        "// trailing",
+        "/* block; }\n   comment */",
+    ]);
+
+    let errors = check("a = 1; /* b = 2;");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].to_string(), "expected `*/` to close the block comment, found end of input");
+}
+
+#[test]
+fn test_string_literal() {
+    let mut env = herang::HeEnv::new();
+    init_env(&mut env).unwrap();
+
+    let result = eval(r#""Hi!";"#, &mut env);
+    assert_eq!(result.unwrap().value, vec![72, 105, 33]);
+
+    let result = eval(r#""a\n\t\"\\\x41é" | "";"#, &mut env);
+    assert_eq!(result.unwrap().value, vec![97, 10, 9, 34, 92, 65, 195, 169]);
+
+    let result = eval(r"'a' | '\'' | '\0';", &mut env);
+    assert_eq!(result.unwrap().value, vec![97, 39, 0]);
+
+    // A `;` inside a string does not end the statement when recovering.
+    let errors = check(r#"a = "x;y" +; b = "\q";"#);
+    let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+    assert_eq!(messages, vec![
+        "expected an expression after the operator, found `;`",
+        r#"expected an escape such as `\n`, `\"` or `\x41`, found `q`"#,
+    ]);
+}
+
+#[test]
+fn test_wrapping() {
+    let mut env = herang::HeEnv::new();
+    init_env(&mut env).unwrap();
+
+    let result = eval("(250 | 3) + 10;", &mut env);
+    assert_eq!(result.unwrap().value, vec![4, 13]);
+    let result = eval("3 - 5;", &mut env);
+    assert_eq!(result.unwrap().value, vec![254]);
+    let result = eval("(16 | 1) * (17 | 17) | 1 << 8;", &mut env);
+    assert_eq!(result.unwrap().value, vec![16, 17, 0]);
+    let result = eval("a = 1 | 256;", &mut env);
+    assert_eq!(result.unwrap_err(), HeError::LiteralOutOfRange { value: 256, numeric: Numeric::default(), span: Span::new(8, 11) });
+
+    env.set_numeric(Numeric::new(16).unwrap());
+    let result = eval("(250 + 10) | (3 - 5) | 256;", &mut env);
+    assert_eq!(result.unwrap().value, vec![260, 65534, 256]);
+    let result = eval("65535 + 1;", &mut env);
+    assert_eq!(result.unwrap().value, vec![0]);
+
+    assert_eq!(Numeric::new(12), None);
+}
+
+#[test]
+fn test_length_rules() {
+    let mut env = herang::HeEnv::new();
+    init_env(&mut env).unwrap();
+
+    let result = eval("(1 | 2 | 3) + (10 | 20 | 30);", &mut env);
+    assert_eq!(result.unwrap().value, vec![11, 22, 33]);
+    // A single element is repeated on either side.
+    let result = eval("(10 | 20 | 30) - 1;", &mut env);
+    assert_eq!(result.unwrap().value, vec![9, 19, 29]);
+    let result = eval("100 - (10 | 20 | 30);", &mut env);
+    assert_eq!(result.unwrap().value, vec![90, 80, 70]);
+    let result = eval("2 * cyber(0);", &mut env);
+    assert_eq!(result.unwrap().value, vec![]);
+
+    let result = eval("(1 | 2 | 3) + (1 | 2);", &mut env);
+    assert_eq!(result.unwrap_err(), HeError::LengthMismatch { left: 3, right: 2, span: Span::new(12, 13) });
+    let result = eval("cyber(2) / cyber(0);", &mut env);
+    assert!(matches!(result, Err(HeError::LengthMismatch { left: 2, right: 0, .. })));
+
+    // Lengths and indexs do not wrap around.
+    let result = eval("len(0..=254) | count(0..=254, 0);", &mut env);
+    assert_eq!(result.unwrap().value, vec![255, 1]);
+    let result = eval("len(0..=255);", &mut env);
+    assert_eq!(result.unwrap_err(), HeError::LengthOutOfRange { len: 256, numeric: Numeric::default(), span: Span::new(0, 12) });
+    let result = eval("find($(x) { 0; }, 0..=255);", &mut env);
+    assert!(matches!(result, Err(HeError::LengthOutOfRange { .. })));
+}
+
+#[test]
+fn test_signed() {
+    let mut env = herang::HeEnv::new();
+    init_env(&mut env).unwrap();
+
+    // Unsigned, unary minus wraps around.
+    let result = eval("-1 | -(2 | 3) | - -7;", &mut env);
+    assert_eq!(result.unwrap().value, vec![255, 254, 253, 7]);
+    let result = eval("3 - 5 < 0;", &mut env);
+    assert_eq!(result.unwrap().value, vec![0]);
+    let result = eval("-[1, 2];", &mut env);
+    assert_eq!(result.unwrap_err(), HeError::TypeMismatch { expected: "number", found: "list", span: Span::new(0, 7) });
+    let result = eval("-{1: 2};", &mut env);
+    assert!(matches!(result, Err(HeError::TypeMismatch { expected: "number", found: "map", .. })));
+
+    env.set_numeric(Numeric::new(8).unwrap().with_signed(true));
+    let result = eval("(3 - 5 < 0) | (-1 < 1) | ((0 | -1) < (0 | 1));", &mut env);
+    assert_eq!(result.unwrap().value, vec![1, 1, 1]);
+    // Elements are stored in two's complement.
+    let result = eval("-7 / 2 | -7 % 2 | -8 >> 1 | -3 * -3;", &mut env);
+    let numbers: Vec<i64> = result.unwrap().value.into_iter()
+        .map(|x| env.numeric().number(x))
+        .collect();
+    assert_eq!(numbers, vec![-3, -1, -4, 9]);
+    let result = eval("128;", &mut env);
+    assert!(matches!(result, Err(HeError::LiteralOutOfRange { value: 128, .. })));
+    assert_eq!(env.numeric().format(&herang::Value::new(vec![254, 2])), "(-2 | 2)");
+    // Bytes of strings and characters are taken as they are.
+    let result = eval("sprint(\"é\"); \"é\" | '\\xff';", &mut env);
+    assert_eq!(env.numeric().format(&result.unwrap()), "(-61 | -87 | -1)");
+}
+
+#[test]
+fn test_lambda() {
+    let mut env = herang::HeEnv::new();
+    init_env(&mut env).unwrap();
+
+    let result = eval("$twice(f, x) { f(f(x)); }; $inc(x) { x + 1; }; twice(inc, 5);", &mut env);
+    assert_eq!(result.unwrap().value, vec![7]);
+    let result = eval("square = $(x) { x * x; }; twice(square, 3);", &mut env);
+    assert_eq!(result.unwrap().value, vec![81]);
+    // An argument holding a function shadows the `$` function of that name.
+    let result = eval("$apply(len, x) { len(x); }; apply(inc, 1 | 2) | len(1 | 2);", &mut env);
+    assert_eq!(result.unwrap().value, vec![2, 3, 2]);
+
+    let result = eval("map(square, 1 | 2 | 3) | map(len, 1 | 2);", &mut env);
+    assert_eq!(result.unwrap().value, vec![1, 4, 9, 1, 1]);
+    let result = eval("filter($(x) { x % 2; }, 1 | 2 | 3 | 4 | 5);", &mut env);
+    assert_eq!(result.unwrap().value, vec![1, 3, 5]);
+    let result = eval("fold($(sum, x) { sum + x; }, 0, 1 | 2 | 3 | 4);", &mut env);
+    assert_eq!(result.unwrap().value, vec![10]);
+
+    let result = eval("x = 1; x(2);", &mut env);
+    assert_eq!(result.unwrap_err(), HeError::TypeMismatch { expected: "function", found: "number", span: Span::new(7, 11) });
+    let result = eval("square + 1;", &mut env);
+    assert!(matches!(result, Err(HeError::TypeMismatch { expected: "number", found: "function", .. })));
+    let result = eval("map(1, 2);", &mut env);
+    assert!(matches!(result, Err(HeError::TypeMismatch { expected: "function", .. })));
+}
+
+#[test]
+fn test_closure() {
+    let mut env = herang::HeEnv::new();
+    init_env(&mut env).unwrap();
+
+    // Functions see the variables where they were defined, not their caller's.
+    let result = eval("x = 1; $show() { x; }; $caller() { def x; x = 2; show(); }; caller();", &mut env);
+    assert_eq!(result.unwrap().value, vec![1]);
+    let result = eval("$adder(n) { $(x) { x + n; }; }; addThree = adder(3); addThree(4);", &mut env);
+    assert_eq!(result.unwrap().value, vec![7]);
+    let result = eval("$counter() { def c; c = 0; $(x) { c = c + x; }; }; k = counter(); k(1); k(2); k(3);", &mut env);
+    assert_eq!(result.unwrap().value, vec![6]);
+    let result = eval("$fact(n) { ?(n <= 1) { return 1; }; n * fact(n - 1); }; fact(5);", &mut env);
+    assert_eq!(result.unwrap().value, vec![120]);
+
+    let mut env = herang::HeEnv::new();
+    init_env(&mut env).unwrap();
+    env.set_dynamic_scope(true);
+    let result = eval("x = 1; $show() { x; }; $caller() { def x; x = 2; show(); }; caller();", &mut env);
+    assert_eq!(result.unwrap().value, vec![2]);
+
+    // A `$` function returned from the function it is defined in.
+    let mut env = herang::HeEnv::new();
+    init_env(&mut env).unwrap();
+    let input = concat!(
+        "$adder(n) { $add(x) { x + n; }; add; }; f = adder(3); print(f(4));",
+        "$fact(n) { $go(k) { ?(k <= 1) { return 1; }; k * go(k - 1); }; go(n); }; print(fact(5));",
+        "c = 0; $count(x) { c = c + x; }; count(2); count(3); print(c);",
+        "$g() { def y; y = 1; $h() { y = y + 1; }; h(); h(); y; }; print(g());",
+        "$counter() { def n; n = 0; $(x) { n = n + x; }; }; k = counter(); k(1); print(k(2));",
+    );
+    if let Some(output) = run_cpp("closure", input, &mut env) {
+        assert_eq!(output, "(7)\n(120)\n(5)\n(3)\n(3)\n");
+    }
+}
+
+#[test]
+fn test_list() {
+    let mut env = herang::HeEnv::new();
+    init_env(&mut env).unwrap();
+
+    let result = eval("m = [1 | 2, 3 | 4, [5]]; m[1];", &mut env);
+    assert_eq!(result.unwrap().value, vec![3, 4]);
+    let result = eval("m[2][0][0] | m[0][1] | len(m);", &mut env);
+    assert_eq!(result.unwrap().value, vec![5, 2, 3]);
+    let result = eval("m[0 | 2];", &mut env);
+    assert_eq!(result.unwrap().to_string(), "[(1 | 2), [(5)]]");
+    let result = eval("m[2][0] = 6; m[0] = 7; m;", &mut env);
+    assert_eq!(result.unwrap().to_string(), "[(7), (3 | 4), [(6)]]");
+    // Lists are values: assigning to a copy leaves the original alone.
+    let result = eval("a = [1]; b = a; b[0] = 2; a | b;", &mut env);
+    assert_eq!(result.unwrap().to_string(), "[(1), (2)]");
+
+    let result = eval("q = [] | 1 | (2 | 3); q | [[4]];", &mut env);
+    assert_eq!(result.unwrap().to_string(), "[(1), (2 | 3), [(4)]]");
+    let result = eval("def s; s = 0; @(x : [1 | 2, 3]) { s = s + len(x); }; s;", &mut env);
+    assert_eq!(result.unwrap().value, vec![3]);
+    let result = eval("map($(x) { x[0]; }, [1 | 2, 3]);", &mut env);
+    assert_eq!(result.unwrap().to_string(), "[(1), (3)]");
+    let result = eval("([1] == [1]) | ([1] < [1, 0]) | ([] < [0]) | ((1 | 2) < []) | !([]);", &mut env);
+    assert_eq!(result.unwrap().value, vec![1, 1, 1, 1, 1]);
+
+    let result = eval("[1] + 1;", &mut env);
+    assert!(matches!(result, Err(HeError::TypeMismatch { expected: "number", found: "list", .. })));
+    let result = eval("m[3];", &mut env);
+    assert!(matches!(result, Err(HeError::IndexOutOfRange { index: 3, len: 3, .. })));
+    let result = eval("m[1][0][0] = 1;", &mut env);
+    assert!(matches!(result, Err(HeError::TypeMismatch { expected: "list", found: "number", .. })));
+
+    // Variables may be named like what the C++ header defines.
+    let mut env = herang::HeEnv::new();
+    init_env(&mut env).unwrap();
+    let input = concat!(
+        "list = [1, 2]; list[0] = 7; print(list); range = 0..3; print(range);",
+        "elements = 1 | 2; @(x : elements) { print(x); };",
+        "$f(function, dict) { function + dict; }; print(f(1, 2));",
+    );
+    if let Some(output) = run_cpp("names", input, &mut env) {
+        assert_eq!(output, "[(7), (2)]\n(0 | 1 | 2)\n(1)\n(2)\n(3)\n");
+    }
+}
+
+#[test]
+fn test_map() {
+    let mut env = herang::HeEnv::new();
+    init_env(&mut env).unwrap();
+
+    let result = eval("m = {1: 2, 3 | 4: [5], 1: 6}; m[1] | len(m);", &mut env);
+    assert_eq!(result.unwrap().value, vec![6, 2]);
+    let result = eval("m[3 | 4][0];", &mut env);
+    assert_eq!(result.unwrap().value, vec![5]);
+    let result = eval("m[0] = 7; m[3 | 4][0] = 8; m;", &mut env);
+    assert_eq!(result.unwrap().to_string(), "{(0): (7), (1): (6), (3 | 4): [(8)]}");
+    let result = eval("def s; s = 0; @(k : m) { s = s + len(k); }; s;", &mut env);
+    assert_eq!(result.unwrap().value, vec![4]);
+
+    let result = eval("get(m, 1, 0) | get(m, 2, 0) | contains(m, 0) | contains(m, 2);", &mut env);
+    assert_eq!(result.unwrap().value, vec![6, 0, 1, 0]);
+    let result = eval("n = remove(set(m, 2, 9), 0); keys(n);", &mut env);
+    assert_eq!(result.unwrap().to_string(), "[(1), (2), (3 | 4)]");
+    // `set` and `remove` leave their argument alone.
+    let result = eval("len(m) | len(n) | (m == {0: 7, 1: 6, 3 | 4: [8]}) | !{};", &mut env);
+    assert_eq!(result.unwrap().value, vec![3, 3, 1, 1]);
+
+    let result = eval("m[2];", &mut env);
+    assert_eq!(result.unwrap_err(), HeError::KeyNotFound { key: herang::Value::new(vec![2]), span: Span::new(0, 4) });
+    let result = eval("m | 1;", &mut env);
+    assert!(matches!(result, Err(HeError::TypeMismatch { expected: "number", found: "map", .. })));
+    let result = eval("get(1, 2, 3);", &mut env);
+    assert!(matches!(result, Err(HeError::TypeMismatch { expected: "map", found: "number", .. })));
+}
+
+#[test]
+fn test_range() {
+    let mut env = herang::HeEnv::new();
+    init_env(&mut env).unwrap();
+
+    let result = eval("0..4 | 1..=3 | 0..10 step 4 | 5..2;", &mut env);
+    assert_eq!(result.unwrap().value, vec![0, 1, 2, 3, 1, 2, 3, 0, 4, 8]);
+    // Bounds bind tighter than `|` and looser than arithmetic.
+    let result = eval("n = 3; 1..n + 1 | 7;", &mut env);
+    assert_eq!(result.unwrap().value, vec![1, 2, 3, 7]);
+    let result = eval("s = 10 | 20 | 30 | 40; s[1..3];", &mut env);
+    assert_eq!(result.unwrap().value, vec![20, 30]);
+    let result = eval("def t; t = 0; @(i : 0..=255) { t = t + 1; }; t;", &mut env);
+    assert_eq!(result.unwrap().value, vec![0]);
+
+    let result = eval("0..(1 | 2);", &mut env);
+    assert!(matches!(result, Err(HeError::InvalidRange { .. })));
+    let result = eval("0..5 step 0;", &mut env);
+    assert_eq!(result.unwrap_err(), HeError::InvalidRange { reason: "step is 0".to_string(), span: Span::new(1, 3) });
+
+    env.set_numeric(Numeric::new(8).unwrap().with_signed(true));
+    let result = eval("3..=-3 step -3;", &mut env);
+    assert_eq!(env.numeric().format(&result.unwrap()), "(3 | 0 | -3)");
+}
+
+#[test]
+fn test_import() {
+    let mut env = herang::HeEnv::new();
+    init_env(&mut env).unwrap();
+    env.set_file(PathBuf::from("tests/modules/main.her"));
+
+    let result = eval("import \"util.her\"; util::twice(1 | 2);", &mut env);
+    assert_eq!(result.unwrap().value, vec![1, 2, 1, 2]);
+    assert!(check("@(util::twice(1)) { break; };").is_empty());
+    // `import` is only a statement once a path or `from` follows.
+    let result = eval("import = 1; import + 1;", &mut env);
+    assert_eq!(result.unwrap().value, vec![2]);
+    assert_eq!(check("import twice from util;").len(), 1);
+    let result = eval("n = 0; @(util::twice(n < 3)) { n = n + 1; }; n;", &mut env);
+    assert_eq!(result.unwrap().value, vec![3]);
+    // Functions keep seeing the variables of their own file.
+    let result = eval("base = 0; import \"util.her\" as u; u::add_base(1);", &mut env);
+    assert_eq!(result.unwrap().value, vec![11]);
+    let result = eval("import twice from \"util.her\"; f = twice; f(3);", &mut env);
+    assert_eq!(result.unwrap().value, vec![3, 3]);
+    let result = eval("add_base(1);", &mut env);
+    assert!(matches!(result, Err(HeError::UndefinedFunction { .. })));
+
+    let result = eval("import thrice from \"util.her\";", &mut env);
+    assert_eq!(result.unwrap_err(), HeError::ImportFailed {
+        path: "util.her".to_string(),
+        reason: "it has no function thrice".to_string(),
+        span: Span::new(0, 29),
+    });
+    let result = eval("import \"cycle_a.her\";", &mut env);
+    assert!(matches!(result, Err(HeError::ImportFailed { reason, .. }) if reason.contains("import cycle cycle_a.her -> cycle_b.her -> cycle_a.her")));
+    let result = eval("import \"missing.her\";", &mut env);
+    assert!(matches!(result, Err(HeError::ImportFailed { .. })));
+
+    let mut env = herang::HeEnv::new();
+    init_env(&mut env).unwrap();
+    env.set_file(PathBuf::from("tests/modules/main.her"));
+    let code = gen_code("import \"util.her\"; import twice from \"util.her\"; twice(1);", &mut env).unwrap();
+    assert_eq!(code.matches("// tests/modules/util.her").count(), 1);
+    assert!(code.contains("static std::shared_ptr<u8> base = std::make_shared<u8>();"));
+    assert!(code.contains("u8 &util__twice = _import_1_twice;"));
+}
+
+#[test]
+fn test_prelude() {
+    let mut env = herang::HeEnv::new();
+    init_env(&mut env).unwrap();
+
+    let result = eval("reverse(1 | 2 | 3) | pop(4 | 5) | sum(iota(4)) | max(3 | 9 | 1);", &mut env);
+    assert_eq!(result.unwrap().value, vec![3, 2, 1, 4, 6, 9]);
+    let result = eval("reverse([1, 2 | 3]);", &mut env);
+    assert_eq!(result.unwrap(), Value::list(vec![Value::new(vec![2, 3]), Value::new(vec![1])]));
+    let result = eval("join(split(\"a-b-c\", '-'), \"+\");", &mut env);
+    assert_eq!(result.unwrap().value, "a+b+c".bytes().map(u32::from).collect::<Vec<_>>());
+    let result = eval("index_of(\"abc\", 'c') | index_of(\"abc\", 'd') | contains([1, 2], 2);", &mut env);
+    assert_eq!(result.unwrap().value, vec![2, 3, 1]);
+    // The program can define the same names.
+    let result = eval("$front(s) { 7; }; front(1 | 2);", &mut env);
+    assert_eq!(result.unwrap().value, vec![7]);
+
+    let mut env = herang::HeEnv::new();
+    init_env(&mut env).unwrap();
+    if let Some(output) = run_cpp("prelude", "$front(s) { 7; }; print(front(1 | 2) | back(1 | 2));", &mut env) {
+        assert_eq!(output, "(7 | 2)\n");
+    }
+
+    let mut env = herang::HeEnv::new();
+    init_env(&mut env).unwrap();
+    env.set_prelude(false);
+    let result = eval("front(1 | 2);", &mut env);
+    assert!(matches!(result, Err(HeError::UndefinedFunction { .. })));
+}
+
+#[test]
+fn test_sort_search_set() {
+    let mut env = herang::HeEnv::new();
+    init_env(&mut env).unwrap();
+
+    let result = eval("sort(3 | 1 | 2) | sort_desc(3 | 1 | 2) | unique(1 | 2 | 1 | 3);", &mut env);
+    assert_eq!(result.unwrap().value, vec![1, 2, 3, 3, 2, 1, 1, 2, 3]);
+    let result = eval("sort([2, 1 | 1, 3]);", &mut env);
+    assert_eq!(result.unwrap(), Value::list(vec![Value::new(vec![1, 1]), Value::new(vec![2]), Value::new(vec![3])]));
+    let result = eval("binary_search(1 | 3 | 5, 3) | binary_search(1 | 3 | 5, 4) | binary_search(1 | 3 | 5, 9);", &mut env);
+    assert_eq!(result.unwrap().value, vec![1, 2, 3]);
+    let result = eval("min(4 | 2 | 8) | max(4 | 2 | 8) | sum(1 | 2 | 3) | count(\"hello\", 'l');", &mut env);
+    assert_eq!(result.unwrap().value, vec![2, 8, 6, 2]);
+    let result = eval("find($(x) { x > 2; }, 1 | 5 | 3) | find($(x) { x > 9; }, 1 | 2);", &mut env);
+    assert_eq!(result.unwrap().value, vec![1, 2]);
+    let result = eval("union(1 | 2, 2 | 3) | intersect(1 | 2 | 3, 3 | 2) | difference(1 | 2 | 3, 2);", &mut env);
+    assert_eq!(result.unwrap().value, vec![1, 2, 3, 2, 3, 1, 3]);
+    let result = eval("intersect([3, 1 | 2, [4]], [[4], 1 | 2]) | [count([1, [1], 1], 1)];", &mut env);
+    assert_eq!(result.unwrap().to_string(), "[(1 | 2), [(4)], (2)]");
+    // Items are equal as they are ordered, as in `count`.
+    let result = eval("f = $(x) { x; }; g = $(x) { 1; }; contains([f], g) | count([f], g);", &mut env);
+    assert_eq!(result.unwrap().value, vec![1, 1]);
+
+    env.set_numeric(Numeric::new(8).unwrap().with_signed(true));
+    let result = eval("sort(2 | -1) | min(2 | -1);", &mut env);
+    assert_eq!(env.numeric().format(&result.unwrap()), "(-1 | 2 | -1)");
+
+    let result = eval("min(cyber(0));", &mut env);
+    assert_eq!(result.unwrap_err(), HeError::InvalidArgument { reason: "empty sequence".to_string(), span: Span::new(0, 13) });
+    let result = eval("sort(1, 2);", &mut env);
+    assert!(matches!(result, Err(HeError::ArityMismatch { expected: Arity::Exact(1), got: 2, .. })));
+    let result = eval("find(1, 2);", &mut env);
+    assert!(matches!(result, Err(HeError::TypeMismatch { expected: "function", .. })));
+}
+
+#[test]
+fn test_number_text() {
+    let mut env = herang::HeEnv::new();
+    init_env(&mut env).unwrap();
+
+    let result = eval("parse_int(\"123\", 10) | parse_int(\"ff\", 16) | parse_int(\"101\", 2);", &mut env);
+    assert_eq!(result.unwrap().value, vec![123, 255, 5]);
+    let result = eval("parse_int(\"9021\", 10, \"be\") | parse_int(\"9021\", 10, \"le\");", &mut env);
+    assert_eq!(result.unwrap().value, vec![9, 0, 2, 1, 1, 2, 0, 9]);
+    let result = eval("to_str(200, 10) | \" \" | to_str(255, 16) | \" \" | to_str(1 | 2 | 3, 10, \"le\");", &mut env);
+    assert_eq!(result.unwrap().value, "200 ff 321".bytes().map(u32::from).collect::<Vec<_>>());
+    let result = eval("fmt(\"{} + {} = {}\", 1, 2, [3]);", &mut env);
+    assert_eq!(result.unwrap().value, "1 + 2 = [(3)]".bytes().map(u32::from).collect::<Vec<_>>());
+
+    let result = eval("parse_int(\"12a\", 10);", &mut env);
+    assert_eq!(result.unwrap_err(), HeError::InvalidNumber {
+        reason: "`a` is not a digit in base 10".to_string(),
+        span: Span::new(0, 20),
+    });
+    let result = eval("parse_int(\"256\", 10);", &mut env);
+    assert!(matches!(result, Err(HeError::InvalidNumber { .. })));
+    let result = eval("to_str(12, 10, \"le\");", &mut env);
+    assert!(matches!(result, Err(HeError::InvalidNumber { .. })));
+    let result = eval("parse_int(\"1\", 10, \"le\", 0);", &mut env);
+    assert!(matches!(result, Err(HeError::ArityMismatch { expected: Arity::Between(2, 3), got: 4, .. })));
+    let result = eval("fmt(\"{}\", 1, 2);", &mut env);
+    assert!(matches!(result, Err(HeError::ArityMismatch { expected: Arity::Exact(2), got: 3, .. })));
+
+    env.set_numeric(Numeric::new(8).unwrap().with_signed(true));
+    let result = eval("parse_int(\"-128\", 10) | to_str(-5, 10);", &mut env);
+    assert_eq!(env.numeric().format(&result.unwrap()), "(-128 | 45 | 53)");
+}
+
+#[test]
+fn test_big() {
+    let mut env = herang::HeEnv::new();
+    init_env(&mut env).unwrap();
+
+    let result = eval("bigadd(9 | 9 | 9, 1) | bigsub(0 | 0 | 1, 1) | bigmul(2 | 1, 2 | 1);", &mut env);
+    assert_eq!(result.unwrap().value, vec![0, 0, 0, 1, 9, 9, 4, 4, 1]);
+    let result = eval("to_str(bigpow(2, 100), 10, \"le\");", &mut env);
+    assert_eq!(result.unwrap().value, "1267650600228229401496703205376".bytes().map(u32::from).collect::<Vec<_>>());
+    let result = eval("d = bigdivmod(parse_int(\"1234567\", 10, \"le\"), 4 | 3 | 2); to_str(d[0], 10, \"le\") | ' ' | to_str(d[1], 10, \"le\");", &mut env);
+    assert_eq!(result.unwrap().value, "5275 217".bytes().map(u32::from).collect::<Vec<_>>());
+    let result = eval("bigcmp(1 | 2, 2 | 1) | bigcmp(1 | 0, 1) | bigcmp(1 | 1, 0 | 2);", &mut env);
+    assert_eq!(result.unwrap().value, vec![2, 1, 0]);
+    if let Some(output) = run_cpp("bigcmp", "print(bigcmp(1 | 2, 2 | 1) | bigcmp(1 | 0, 1) | bigcmp(1 | 1, 0 | 2));", &mut env) {
+        assert_eq!(output, "(2 | 1 | 0)\n");
+    }
+    // 0 as the base makes every element a digit, base 256 for 8 bits.
+    let result = eval("bigadd(255 | 255, 1, 0) | bigmul(255, 255, 0);", &mut env);
+    assert_eq!(result.unwrap().value, vec![0, 0, 1, 1, 254]);
+
+    let result = eval("bigsub(1, 2);", &mut env);
+    assert!(matches!(result, Err(HeError::InvalidNumber { .. })));
+    let result = eval("bigdivmod(1, 0 | 0);", &mut env);
+    assert!(matches!(result, Err(HeError::DivisionByZero { .. })));
+    let result = eval("bigadd(12, 1);", &mut env);
+    assert_eq!(result.unwrap_err(), HeError::InvalidNumber {
+        reason: "digit 12 is not below base 10".to_string(),
+        span: Span::new(0, 13),
+    });
+}